@@ -1,9 +1,12 @@
 use log::{error, info, warn};
+#[cfg(windows)]
 use std::ffi::OsStr;
+#[cfg(windows)]
 use std::os::windows::ffi::OsStrExt;
 use std::path::PathBuf;
 use std::time::Duration;
 use tokio::time::sleep;
+#[cfg(windows)]
 use std::process::Command;
 
 
@@ -11,7 +14,7 @@ fn get_current_exe_path() -> Result<PathBuf, String> {
     std::env::current_exe().map_err(|e| format!("Failed to get current exe path: {}", e))
 }
 
-
+#[cfg(windows)]
 fn check_autostart() -> Result<bool, String> {
     let exe_path = get_current_exe_path()?;
     let exe_str = exe_path.to_str().unwrap_or("");
@@ -27,6 +30,7 @@ fn check_autostart() -> Result<bool, String> {
     let stdout = String::from_utf8_lossy(&output.stdout).to_string();
     Ok(stdout.contains(exe_str))
 }
+#[cfg(windows)]
 pub fn set_autostart() -> Result<(), String> {
     let exe_path = get_current_exe_path()?;
     let exe_path_str = exe_path.to_str().unwrap_or("");
@@ -56,6 +60,47 @@ pub fn set_autostart() -> Result<(), String> {
     }
 }
 
+#[cfg(not(windows))]
+fn autostart_desktop_file() -> Result<PathBuf, String> {
+    let config_dir = std::env::var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| std::env::var("HOME").map(|h| PathBuf::from(h).join(".config")))
+        .map_err(|_| "Failed to determine XDG config directory".to_string())?;
+    Ok(config_dir.join("autostart").join("daily-wallpaper.desktop"))
+}
+
+#[cfg(not(windows))]
+fn autostart_desktop_entry(exe_path_str: &str) -> String {
+    format!(
+        "[Desktop Entry]\nType=Application\nName=Daily Wallpaper\nExec=\"{}\"\nX-GNOME-Autostart-enabled=true\nNoDisplay=true\n",
+        exe_path_str
+    )
+}
+
+#[cfg(not(windows))]
+fn check_autostart() -> Result<bool, String> {
+    let exe_path = get_current_exe_path()?;
+    let exe_str = exe_path.to_str().unwrap_or("");
+    match std::fs::read_to_string(autostart_desktop_file()?) {
+        Ok(content) => Ok(content == autostart_desktop_entry(exe_str)),
+        Err(_) => Ok(false),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn set_autostart() -> Result<(), String> {
+    let exe_path = get_current_exe_path()?;
+    let exe_path_str = exe_path.to_str().unwrap_or("");
+    let desktop_file = autostart_desktop_file()?;
+    if let Some(dir) = desktop_file.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create autostart directory: {}", e))?;
+    }
+    std::fs::write(&desktop_file, autostart_desktop_entry(exe_path_str))
+        .map_err(|e| format!("Failed to write {}: {}", desktop_file.display(), e))?;
+    info!("Autostart set successfully for: {}", exe_path_str);
+    Ok(())
+}
+
 pub async fn check_loop() {
     loop {
         match check_autostart() {
@@ -73,6 +118,7 @@ pub async fn check_loop() {
         sleep(Duration::from_secs(3600)).await;
     }
 }
+#[cfg(windows)]
 pub fn to_wide_string(s: &str) -> Vec<u16> {
     OsStr::new(s).encode_wide().chain(Some(0)).collect()
 }
\ No newline at end of file
@@ -0,0 +1,650 @@
+use crate::clock::Clock;
+use crate::schedule::MatchedRule;
+use chrono::{DateTime, Local};
+use log::{debug, info};
+#[cfg(unix)]
+use log::warn;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+#[cfg(unix)]
+use std::process::Child;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WallpaperSource {
+    pub url: String,
+    pub rule: MatchedRule,
+}
+
+pub trait WallpaperBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn set_image(&self, path: &Path, source: &WallpaperSource) -> Result<(), String>;
+    /// Returns `Ok(false)` when the page should be shown by launching `daily_web` instead.
+    fn set_web_page(&self, _url: &str, _source: &WallpaperSource) -> Result<bool, String> {
+        Ok(false)
+    }
+    fn clear(&self) -> Result<(), String>;
+    fn set_lock(&self, lock: bool) -> Result<(), String> {
+        debug!("{} backend does not support wallpaper locking (requested: {})", self.name(), lock);
+        Ok(())
+    }
+    fn current(&self) -> Result<Option<PathBuf>, String>;
+    /// The screen in pixels, which locally drawn wallpapers are rendered at.
+    fn screen_size(&self) -> Option<(u32, u32)> {
+        None
+    }
+    /// Recently applied wallpapers, oldest first. Only kept by backends that record them.
+    fn history(&self) -> Vec<AppliedWallpaper> {
+        Vec::new()
+    }
+}
+
+#[cfg(windows)]
+pub fn detect_backend() -> Result<Box<dyn WallpaperBackend>, String> {
+    Ok(Box::new(WindowsBackend))
+}
+
+#[cfg(unix)]
+pub fn detect_backend() -> Result<Box<dyn WallpaperBackend>, String> {
+    let desktop = std::env::var("XDG_CURRENT_DESKTOP")
+        .or_else(|_| std::env::var("XDG_SESSION_DESKTOP"))
+        .or_else(|_| std::env::var("DESKTOP_SESSION"))
+        .unwrap_or_default()
+        .to_lowercase();
+    debug!("Detected desktop session: '{}'", desktop);
+
+    let is_desktop = |name: &str| desktop.split(':').any(|d| d == name);
+    if is_desktop("gnome") || is_desktop("unity") || is_desktop("budgie") || is_desktop("pantheon") {
+        return Ok(Box::new(GnomeBackend));
+    }
+    if is_desktop("kde") || is_desktop("plasma") {
+        return Ok(Box::new(KdeBackend));
+    }
+    if is_desktop("sway") || std::env::var_os("SWAYSOCK").is_some() {
+        return Ok(Box::new(SwayBackend::new(true)));
+    }
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() && command_exists("swaybg") {
+        return Ok(Box::new(SwayBackend::new(false)));
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        for tool in ["feh", "xwallpaper"] {
+            if command_exists(tool) {
+                return Ok(Box::new(X11Backend::new(tool)));
+            }
+        }
+        return Err("X11 session found but neither feh nor xwallpaper is installed".to_string());
+    }
+    Err(format!("No wallpaper backend available for desktop session '{}'", desktop))
+}
+
+#[cfg(not(any(windows, unix)))]
+pub fn detect_backend() -> Result<Box<dyn WallpaperBackend>, String> {
+    Err("No wallpaper backend available for this platform".to_string())
+}
+
+#[cfg(windows)]
+pub struct WindowsBackend;
+
+#[cfg(windows)]
+impl WindowsBackend {
+    fn set_desk_wallpaper(path: &str) -> Result<(), String> {
+        use crate::autostart::to_wide_string;
+        use windows::Win32::UI::WindowsAndMessaging::{
+            SystemParametersInfoW, SPI_SETDESKWALLPAPER, SPIF_SENDCHANGE, SPIF_UPDATEINIFILE,
+        };
+
+        let path_wide = to_wide_string(path);
+        let result = unsafe {
+            SystemParametersInfoW(
+                SPI_SETDESKWALLPAPER,
+                0,
+                Some(path_wide.as_ptr() as *mut _),
+                SPIF_UPDATEINIFILE | SPIF_SENDCHANGE,
+            )
+        };
+
+        if result.is_ok() {
+            Ok(())
+        } else {
+            Err("SystemParametersInfoW call failed".to_string())
+        }
+    }
+}
+
+#[cfg(windows)]
+impl WallpaperBackend for WindowsBackend {
+    fn name(&self) -> &'static str {
+        "windows"
+    }
+
+    fn set_image(&self, path: &Path, _source: &WallpaperSource) -> Result<(), String> {
+        let path_str = path.to_str().ok_or("Invalid path string")?;
+        Self::set_desk_wallpaper(path_str)
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        Self::set_desk_wallpaper("")
+    }
+
+    fn set_lock(&self, lock: bool) -> Result<(), String> {
+        let key = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Policies\ActiveDesktop";
+        let value = "NoChangingWallPaper";
+        let data = if lock { "1" } else { "0" };
+        let output = Command::new("reg")
+            .args(["add", key, "/v", value, "/t", "REG_DWORD", "/d", data, "/f"])
+            .output()
+            .map_err(|e| format!("Failed to run reg add for wallpaper lock: {}", e))?;
+
+        if output.status.success() {
+            debug!("Wallpaper lock set to: {}", lock);
+            Ok(())
+        } else {
+            Err(format!("Failed to set NoChangingWallPaper: {}", String::from_utf8_lossy(&output.stderr)))
+        }
+    }
+
+    fn current(&self) -> Result<Option<PathBuf>, String> {
+        use windows::Win32::UI::WindowsAndMessaging::{
+            SystemParametersInfoW, SPI_GETDESKWALLPAPER, SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS,
+        };
+
+        let mut buffer = [0u16; 260];
+        unsafe {
+            SystemParametersInfoW(
+                SPI_GETDESKWALLPAPER,
+                buffer.len() as u32,
+                Some(buffer.as_mut_ptr() as *mut _),
+                SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+            )
+        }
+        .map_err(|e| format!("SystemParametersInfoW call failed: {}", e))?;
+
+        let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+        if len == 0 {
+            return Ok(None);
+        }
+        Ok(Some(PathBuf::from(String::from_utf16_lossy(&buffer[..len]))))
+    }
+
+    fn screen_size(&self) -> Option<(u32, u32)> {
+        use windows::Win32::UI::WindowsAndMessaging::{GetSystemMetrics, SM_CXSCREEN, SM_CYSCREEN};
+
+        let (width, height) = unsafe { (GetSystemMetrics(SM_CXSCREEN), GetSystemMetrics(SM_CYSCREEN)) };
+        (width > 0 && height > 0).then_some((width as u32, height as u32))
+    }
+}
+
+#[cfg(unix)]
+pub struct GnomeBackend;
+
+#[cfg(unix)]
+impl WallpaperBackend for GnomeBackend {
+    fn name(&self) -> &'static str {
+        "gnome"
+    }
+
+    fn set_image(&self, path: &Path, _source: &WallpaperSource) -> Result<(), String> {
+        let uri = path_to_file_uri(path)?;
+        run_command("gsettings", &["set", "org.gnome.desktop.background", "picture-options", "zoom"])?;
+        run_command("gsettings", &["set", "org.gnome.desktop.background", "picture-uri", &uri])?;
+        // Older GNOME releases have no dark variant, so this one is best effort.
+        if let Err(e) = run_command("gsettings", &["set", "org.gnome.desktop.background", "picture-uri-dark", &uri]) {
+            debug!("Could not set picture-uri-dark: {}", e);
+        }
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        run_command("gsettings", &["set", "org.gnome.desktop.background", "picture-options", "none"])
+    }
+
+    fn current(&self) -> Result<Option<PathBuf>, String> {
+        let output = run_command_output("gsettings", &["get", "org.gnome.desktop.background", "picture-uri"])?;
+        Ok(file_uri_to_path(output.trim().trim_matches('\'')))
+    }
+
+    fn screen_size(&self) -> Option<(u32, u32)> {
+        xrandr_screen_size()
+    }
+}
+
+#[cfg(unix)]
+pub struct KdeBackend;
+
+#[cfg(unix)]
+impl KdeBackend {
+    fn evaluate_script(script: &str) -> Result<String, String> {
+        run_command_output(
+            "dbus-send",
+            &[
+                "--session",
+                "--print-reply",
+                "--dest=org.kde.plasmashell",
+                "/PlasmaShell",
+                "org.kde.PlasmaShell.evaluateScript",
+                &format!("string:{}", script),
+            ],
+        )
+    }
+}
+
+#[cfg(unix)]
+impl WallpaperBackend for KdeBackend {
+    fn name(&self) -> &'static str {
+        "kde"
+    }
+
+    fn set_image(&self, path: &Path, _source: &WallpaperSource) -> Result<(), String> {
+        let uri = path_to_file_uri(path)?;
+        let script = format!(
+            r#"desktops().forEach(function (d) {{
+                d.wallpaperPlugin = "org.kde.image";
+                d.currentConfigGroup = ["Wallpaper", "org.kde.image", "General"];
+                d.writeConfig("Image", {uri:?});
+            }});"#
+        );
+        Self::evaluate_script(&script).map(|_| ())
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        let script = r#"desktops().forEach(function (d) {
+                d.wallpaperPlugin = "org.kde.color";
+            });"#;
+        Self::evaluate_script(script).map(|_| ())
+    }
+
+    fn current(&self) -> Result<Option<PathBuf>, String> {
+        let script = r#"var d = desktops()[0];
+            d.currentConfigGroup = ["Wallpaper", "org.kde.image", "General"];
+            print(d.readConfig("Image"));"#;
+        let output = Self::evaluate_script(script)?;
+        // The reply looks like `method return ...\n   string "file:///..."`.
+        let uri = output
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("string "))
+            .map(|s| s.trim_matches('"').trim())
+            .next()
+            .unwrap_or("");
+        Ok(file_uri_to_path(uri))
+    }
+
+    fn screen_size(&self) -> Option<(u32, u32)> {
+        xrandr_screen_size()
+    }
+}
+
+#[cfg(unix)]
+pub struct SwayBackend {
+    use_swaymsg: bool,
+    swaybg: Mutex<Option<Child>>,
+    current: Mutex<Option<PathBuf>>,
+}
+
+#[cfg(unix)]
+impl SwayBackend {
+    fn new(use_swaymsg: bool) -> Self {
+        SwayBackend {
+            use_swaymsg,
+            swaybg: Mutex::new(None),
+            current: Mutex::new(None),
+        }
+    }
+
+    fn kill_swaybg(&self) {
+        if let Some(mut child) = self.swaybg.lock().unwrap().take() {
+            info!("Stopping swaybg (PID: {})", child.id());
+            if let Err(e) = child.kill() {
+                warn!("Failed to stop swaybg: {}", e);
+            }
+            child.wait().ok();
+        }
+    }
+}
+
+#[cfg(unix)]
+impl WallpaperBackend for SwayBackend {
+    fn name(&self) -> &'static str {
+        if self.use_swaymsg { "sway" } else { "swaybg" }
+    }
+
+    fn set_image(&self, path: &Path, _source: &WallpaperSource) -> Result<(), String> {
+        let path_str = path.to_str().ok_or("Invalid path string")?;
+        if self.use_swaymsg {
+            run_command("swaymsg", &["output", "*", "bg", path_str, "fill"])?;
+        } else {
+            let child = Command::new("swaybg")
+                .args(["-i", path_str, "-m", "fill"])
+                .spawn()
+                .map_err(|e| format!("Failed to start swaybg: {}", e))?;
+            // Start the new instance first so there is no flash of an empty background.
+            self.kill_swaybg();
+            *self.swaybg.lock().unwrap() = Some(child);
+        }
+        *self.current.lock().unwrap() = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        if self.use_swaymsg {
+            run_command("swaymsg", &["output", "*", "bg", "#000000", "solid_color"])?;
+        } else {
+            self.kill_swaybg();
+        }
+        *self.current.lock().unwrap() = None;
+        Ok(())
+    }
+
+    fn current(&self) -> Result<Option<PathBuf>, String> {
+        Ok(self.current.lock().unwrap().clone())
+    }
+
+    fn screen_size(&self) -> Option<(u32, u32)> {
+        if !self.use_swaymsg {
+            return None;
+        }
+        let output = run_command_output("swaymsg", &["-t", "get_outputs", "-r"]).ok()?;
+        let outputs: serde_json::Value = serde_json::from_str(&output).ok()?;
+        let mode = outputs
+            .as_array()?
+            .iter()
+            .find(|output| output["active"].as_bool() == Some(true))?
+            .get("current_mode")?;
+        Some((mode["width"].as_u64()? as u32, mode["height"].as_u64()? as u32))
+    }
+}
+
+#[cfg(unix)]
+pub struct X11Backend {
+    tool: &'static str,
+    current: Mutex<Option<PathBuf>>,
+}
+
+#[cfg(unix)]
+impl X11Backend {
+    fn new(tool: &'static str) -> Self {
+        X11Backend {
+            tool,
+            current: Mutex::new(None),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl WallpaperBackend for X11Backend {
+    fn name(&self) -> &'static str {
+        self.tool
+    }
+
+    fn set_image(&self, path: &Path, _source: &WallpaperSource) -> Result<(), String> {
+        let path_str = path.to_str().ok_or("Invalid path string")?;
+        match self.tool {
+            "feh" => run_command("feh", &["--no-fehbg", "--bg-fill", path_str])?,
+            _ => run_command("xwallpaper", &["--zoom", path_str])?,
+        }
+        *self.current.lock().unwrap() = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        match self.tool {
+            "feh" => run_command("xsetroot", &["-solid", "#000000"])?,
+            _ => run_command("xwallpaper", &["--clear"])?,
+        }
+        *self.current.lock().unwrap() = None;
+        Ok(())
+    }
+
+    fn current(&self) -> Result<Option<PathBuf>, String> {
+        Ok(self.current.lock().unwrap().clone())
+    }
+
+    fn screen_size(&self) -> Option<(u32, u32)> {
+        xrandr_screen_size()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AppliedAction {
+    Image,
+    WebPage,
+    Clear,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AppliedWallpaper {
+    pub action: AppliedAction,
+    pub source: Option<WallpaperSource>,
+    pub image: Option<PathBuf>,
+    pub page_url: Option<String>,
+    pub applied_at: DateTime<Local>,
+}
+
+const HISTORY_LIMIT: usize = 100;
+
+/// Never touches a desktop: the resolved wallpaper is written to `current.<ext>` and
+/// described in `current.json` inside `out_dir`, and the last calls are kept in memory.
+pub struct HeadlessBackend {
+    out_dir: PathBuf,
+    clock: Arc<dyn Clock>,
+    history: Mutex<VecDeque<AppliedWallpaper>>,
+    current: Mutex<Option<PathBuf>>,
+}
+
+impl HeadlessBackend {
+    pub fn new(out_dir: PathBuf, clock: Arc<dyn Clock>) -> Result<Self, String> {
+        std::fs::create_dir_all(&out_dir)
+            .map_err(|e| format!("Failed to create {}: {}", out_dir.display(), e))?;
+        Ok(HeadlessBackend {
+            out_dir,
+            clock,
+            history: Mutex::new(VecDeque::new()),
+            current: Mutex::new(None),
+        })
+    }
+
+    fn record(&self, applied: AppliedWallpaper) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(&applied).map_err(|e| e.to_string())?;
+        write_atomically(&self.out_dir.join("current.json"), json.as_bytes())?;
+        *self.current.lock().unwrap() = applied.image.clone();
+        let mut history = self.history.lock().unwrap();
+        if history.len() == HISTORY_LIMIT {
+            history.pop_front();
+        }
+        history.push_back(applied);
+        Ok(())
+    }
+
+    fn remove_current_image(&self) {
+        if let Some(previous) = self.current.lock().unwrap().take() {
+            std::fs::remove_file(previous).ok();
+        }
+    }
+}
+
+impl WallpaperBackend for HeadlessBackend {
+    fn name(&self) -> &'static str {
+        "headless"
+    }
+
+    fn set_image(&self, path: &Path, source: &WallpaperSource) -> Result<(), String> {
+        let extension = path.extension().and_then(|s| s.to_str()).unwrap_or("jpg");
+        let dest = self.out_dir.join(format!("current.{}", extension));
+        let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if self.current.lock().unwrap().as_deref() != Some(dest.as_path()) {
+            self.remove_current_image();
+        }
+        write_atomically(&dest, &bytes)?;
+        info!("Rendered wallpaper to {}", dest.display());
+        self.record(AppliedWallpaper {
+            action: AppliedAction::Image,
+            source: Some(source.clone()),
+            image: Some(dest),
+            page_url: None,
+            applied_at: self.clock.now(),
+        })
+    }
+
+    fn set_web_page(&self, url: &str, source: &WallpaperSource) -> Result<bool, String> {
+        self.remove_current_image();
+        self.record(AppliedWallpaper {
+            action: AppliedAction::WebPage,
+            source: Some(source.clone()),
+            image: None,
+            page_url: Some(url.to_string()),
+            applied_at: self.clock.now(),
+        })?;
+        Ok(true)
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        self.remove_current_image();
+        self.record(AppliedWallpaper {
+            action: AppliedAction::Clear,
+            source: None,
+            image: None,
+            page_url: None,
+            applied_at: self.clock.now(),
+        })
+    }
+
+    fn current(&self) -> Result<Option<PathBuf>, String> {
+        Ok(self.current.lock().unwrap().clone())
+    }
+
+    fn history(&self) -> Vec<AppliedWallpaper> {
+        self.history.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+/// Writes next to `dest` and renames into place, so readers never see half a file.
+pub(crate) fn write_atomically(dest: &Path, bytes: &[u8]) -> Result<(), String> {
+    let tmp = dest.with_extension("tmp");
+    std::fs::write(&tmp, bytes).map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+    std::fs::rename(&tmp, dest).map_err(|e| format!("Failed to move {} into place: {}", dest.display(), e))
+}
+
+#[cfg(unix)]
+fn command_exists(name: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(name).is_file()))
+        .unwrap_or(false)
+}
+
+#[cfg(unix)]
+fn run_command(program: &str, args: &[&str]) -> Result<(), String> {
+    run_command_output(program, args).map(|_| ())
+}
+
+#[cfg(unix)]
+fn run_command_output(program: &str, args: &[&str]) -> Result<String, String> {
+    debug!("Running {} {:?}", program, args);
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(format!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+/// The whole X screen from the first line of `xrandr`, e.g.
+/// `Screen 0: minimum 320 x 200, current 2560 x 1440, maximum 16384 x 16384`.
+#[cfg(unix)]
+fn xrandr_screen_size() -> Option<(u32, u32)> {
+    let output = run_command_output("xrandr", &["--current"]).ok()?;
+    let current = output.lines().next()?.split(", ").find_map(|part| part.strip_prefix("current "))?;
+    let (width, height) = current.split_once(" x ")?;
+    Some((width.trim().parse().ok()?, height.trim().parse().ok()?))
+}
+
+#[cfg(unix)]
+fn path_to_file_uri(path: &Path) -> Result<String, String> {
+    url::Url::from_file_path(path)
+        .map(|u| u.to_string())
+        .map_err(|_| format!("Cannot convert {} to a file URI", path.display()))
+}
+
+#[cfg(unix)]
+fn file_uri_to_path(uri: &str) -> Option<PathBuf> {
+    if uri.is_empty() {
+        return None;
+    }
+    match url::Url::parse(uri) {
+        Ok(u) => u.to_file_path().ok(),
+        Err(_) => Some(PathBuf::from(uri)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use chrono::Duration;
+
+    fn out_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("daily-test-{}-{}", std::process::id(), name));
+        std::fs::remove_dir_all(&dir).ok();
+        dir
+    }
+
+    fn source(url: &str) -> WallpaperSource {
+        WallpaperSource {
+            url: url.to_string(),
+            rule: MatchedRule::Day {
+                weekday: "friday".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn headless_writes_current_image_and_json() {
+        let dir = out_dir("current");
+        let clock = Arc::new(ManualClock::new(Local::now()));
+        let backend = HeadlessBackend::new(dir.join("out"), clock).unwrap();
+        let image = dir.join("cached.png");
+        std::fs::write(&image, b"png bytes").unwrap();
+
+        backend.set_image(&image, &source("https://example.com/a.png")).unwrap();
+        let current = dir.join("out").join("current.png");
+        assert_eq!(std::fs::read(&current).unwrap(), b"png bytes");
+        assert_eq!(backend.current().unwrap(), Some(current.clone()));
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("out").join("current.json")).unwrap()).unwrap();
+        assert_eq!(json["action"], "image");
+        assert_eq!(json["source"]["url"], "https://example.com/a.png");
+        assert!(!dir.join("out").join("current.tmp").exists());
+
+        // A page replaces the image file; the description says which page.
+        assert!(backend.set_web_page("https://example.com/page.html", &source("special1")).unwrap());
+        assert!(!current.exists());
+        assert_eq!(backend.current().unwrap(), None);
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("out").join("current.json")).unwrap()).unwrap();
+        assert_eq!(json["page_url"], "https://example.com/page.html");
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn headless_history_keeps_the_last_hundred() {
+        let dir = out_dir("history");
+        let start = Local::now();
+        let clock = Arc::new(ManualClock::new(start));
+        let backend = HeadlessBackend::new(dir.clone(), clock.clone()).unwrap();
+        for _ in 0..HISTORY_LIMIT + 5 {
+            backend.clear().unwrap();
+            clock.advance(Duration::minutes(1));
+        }
+        let history = backend.history();
+        assert_eq!(history.len(), HISTORY_LIMIT);
+        // The first five were dropped, and the rest stay oldest first.
+        assert_eq!(history[0].applied_at, start + Duration::minutes(5));
+        assert_eq!(history[HISTORY_LIMIT - 1].applied_at, start + Duration::minutes(HISTORY_LIMIT as i64 + 4));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,255 @@
+use crate::config::Config;
+use crate::ics::{self, IcsEvent};
+use crate::recurrence::Recurrence;
+use crate::timezone::Zone;
+use crate::validate::is_url;
+use chrono::{Duration, NaiveDateTime};
+use log::{info, warn};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// An iCalendar feed whose events become special periods.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CalendarSource {
+    /// An http(s) URL, or a file path relative to the app data directory.
+    pub source: String,
+    /// CATEGORIES or SUMMARY values mapped to a wallpaper URL or special_urls id.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub map: HashMap<String, String>,
+    /// Used for events that nothing else maps; events without a target are ignored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_url: Option<String>,
+    /// Filled in when the config is loaded.
+    #[serde(skip)]
+    pub events: Vec<CalendarEvent>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CalendarEvent {
+    pub summary: String,
+    pub target: String,
+    pub start: NaiveDateTime,
+    pub duration: Duration,
+    pub recurrence: Option<Recurrence>,
+    pub exdates: Vec<NaiveDateTime>,
+}
+
+impl CalendarEvent {
+    /// Occurrences overlapping `[from, until)`, as `(start, end)`.
+    pub fn occurrences(&self, from: NaiveDateTime, until: NaiveDateTime) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+        let earliest = from - self.duration;
+        let starts = match &self.recurrence {
+            Some(rule) => rule.starts_between(self.start, &self.exdates, earliest, until),
+            None if self.start >= earliest && self.start < until => vec![self.start],
+            None => Vec::new(),
+        };
+        starts
+            .into_iter()
+            .map(|start| (start, start + self.duration))
+            .filter(|(_, end)| *end > from)
+            .collect()
+    }
+}
+
+/// Every calendar event running at `now`, in config order.
+pub fn active_at(config: &Config, now: NaiveDateTime) -> Vec<(usize, &CalendarEvent, NaiveDateTime, NaiveDateTime)> {
+    let mut active = Vec::new();
+    for (i, calendar) in config.calendars.iter().enumerate() {
+        for event in &calendar.events {
+            for (start, end) in event.occurrences(now, now + Duration::minutes(1)) {
+                if start <= now {
+                    active.push((i, event, start, end));
+                }
+            }
+        }
+    }
+    active
+}
+
+/// The first event start or end in `(now, limit]`.
+pub fn next_boundary(config: &Config, now: NaiveDateTime, limit: NaiveDateTime) -> Option<NaiveDateTime> {
+    config
+        .calendars
+        .iter()
+        .flat_map(|calendar| &calendar.events)
+        .flat_map(|event| event.occurrences(now, limit + Duration::minutes(1)))
+        .flat_map(|(start, end)| [start, end])
+        .filter(|at| *at > now && *at <= limit)
+        .min()
+}
+
+/// Event times are wall times in `zone`, the schedule's zone.
+pub fn parse_calendar(
+    text: &str,
+    calendar: &CalendarSource,
+    special_urls: &HashMap<String, String>,
+    zone: Zone,
+) -> Result<Vec<CalendarEvent>, String> {
+    let parsed = ics::parse_events(text)?;
+    // Instances moved with RECURRENCE-ID replace the original occurrence.
+    let mut moved: HashMap<String, Vec<NaiveDateTime>> = HashMap::new();
+    for event in &parsed {
+        if let (Some(uid), Some(recurrence_id)) = (event.get("UID"), event.get("RECURRENCE-ID"))
+            && let Some((at, _)) = ics::parse_date_time(recurrence_id, event.params("RECURRENCE-ID").unwrap_or(""), zone)
+        {
+            moved.entry(uid.to_string()).or_default().push(at);
+        }
+    }
+
+    let mut events = Vec::new();
+    for event in &parsed {
+        if event.get("STATUS").is_some_and(|status| status.eq_ignore_ascii_case("CANCELLED")) {
+            continue;
+        }
+        let Some((start, all_day)) = event
+            .get("DTSTART")
+            .and_then(|value| ics::parse_date_time(value, event.params("DTSTART").unwrap_or(""), zone))
+        else {
+            continue;
+        };
+        let duration = match (event.get("DTEND"), event.get("DURATION")) {
+            (Some(end), _) => ics::parse_date_time(end, event.params("DTEND").unwrap_or(""), zone).map(|(end, _)| end - start),
+            (None, Some(duration)) => ics::parse_duration(duration),
+            (None, None) if all_day => Some(Duration::days(1)),
+            (None, None) => None,
+        };
+        let Some(duration) = duration.filter(|d| *d > Duration::zero()) else {
+            continue;
+        };
+        let summary = event.summary();
+        let Some(target) = event_target(event, &summary, calendar, special_urls) else {
+            continue;
+        };
+        let recurrence = match event.get("RRULE") {
+            Some(rule) if event.get("RECURRENCE-ID").is_none() => {
+                Some(Recurrence::parse_rrule(rule, zone).map_err(|e| format!("event '{}': {}", summary, e))?)
+            }
+            _ => None,
+        };
+        let mut exdates: Vec<NaiveDateTime> = event
+            .get_all("EXDATE")
+            .flat_map(|(params, value)| value.split(',').filter_map(move |v| ics::parse_date_time(v, params, zone)))
+            .map(|(at, _)| at)
+            .collect();
+        if event.get("RECURRENCE-ID").is_none()
+            && let Some(uid) = event.get("UID")
+        {
+            exdates.extend(moved.get(uid).into_iter().flatten());
+        }
+        events.push(CalendarEvent {
+            summary,
+            target,
+            start,
+            duration,
+            recurrence,
+            exdates,
+        });
+    }
+    Ok(events)
+}
+
+/// The event's URL, then CATEGORIES and SUMMARY through `map`, then a DESCRIPTION that
+/// is itself a URL or special_urls id, then `default_url`.
+fn event_target(
+    event: &IcsEvent,
+    summary: &str,
+    calendar: &CalendarSource,
+    special_urls: &HashMap<String, String>,
+) -> Option<String> {
+    if let Some(url) = event.get("URL").filter(|url| is_url(url)) {
+        return Some(url.to_string());
+    }
+    let categories = event.get("CATEGORIES").map(ics::unescape).unwrap_or_default();
+    for key in categories.split(',').map(str::trim).chain([summary]) {
+        if let Some(target) = calendar.map.get(key) {
+            return Some(target.clone());
+        }
+    }
+    let description = event.get("DESCRIPTION").map(ics::unescape).unwrap_or_default();
+    let description = description.trim();
+    if is_url(description) || special_urls.contains_key(description) {
+        return Some(description.to_string());
+    }
+    calendar.default_url.clone()
+}
+
+fn is_remote(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+/// Loads every calendar, falling back to the last downloaded copy of a remote feed.
+pub async fn load(config: &mut Config, app_data_dir: &Path, client: &Client) -> Vec<String> {
+    let mut errors = Vec::new();
+    let cache_dir = app_data_dir.join("calendars");
+    let zone = Zone::of_config(config);
+    for i in 0..config.calendars.len() {
+        let source = config.calendars[i].source.clone();
+        let text = if is_remote(&source) {
+            fetch(client, &source, &cache_dir).await
+        } else {
+            tokio::fs::read_to_string(app_data_dir.join(&source))
+                .await
+                .map_err(|e| e.to_string())
+        };
+        let result = text.and_then(|text| parse_calendar(&text, &config.calendars[i], &config.special_urls, zone));
+        match result {
+            Ok(events) => {
+                info!("Loaded {} events from calendar {}", events.len(), source);
+                config.calendars[i].events = events;
+            }
+            Err(e) => errors.push(format!("Calendar {}: {}", source, e)),
+        }
+    }
+    errors
+}
+
+/// Local files only, for `simulate`.
+pub fn load_files(config: &mut Config, base_dir: &Path) -> Vec<String> {
+    let mut errors = Vec::new();
+    let zone = Zone::of_config(config);
+    for i in 0..config.calendars.len() {
+        let source = config.calendars[i].source.clone();
+        if is_remote(&source) {
+            errors.push(format!("Calendar {}: remote calendars are not fetched here", source));
+            continue;
+        }
+        let result = std::fs::read_to_string(base_dir.join(&source))
+            .map_err(|e| e.to_string())
+            .and_then(|text| parse_calendar(&text, &config.calendars[i], &config.special_urls, zone));
+        match result {
+            Ok(events) => config.calendars[i].events = events,
+            Err(e) => errors.push(format!("Calendar {}: {}", source, e)),
+        }
+    }
+    errors
+}
+
+async fn fetch(client: &Client, url: &str, cache_dir: &Path) -> Result<String, String> {
+    let cache_path = cache_dir.join(format!("{}.ics", sha256::digest(url)));
+    let downloaded = async {
+        let resp = client.get(url).send().await.map_err(|e| e.to_string())?;
+        if !resp.status().is_success() {
+            return Err(format!("Status {}", resp.status()));
+        }
+        resp.text().await.map_err(|e| e.to_string())
+    }
+    .await;
+    match downloaded {
+        Ok(text) => {
+            if let Err(e) = tokio::fs::create_dir_all(cache_dir).await {
+                warn!("Failed to create {}: {}", cache_dir.display(), e);
+            } else if let Err(e) = tokio::fs::write(&cache_path, &text).await {
+                warn!("Failed to cache calendar {}: {}", url, e);
+            }
+            Ok(text)
+        }
+        Err(e) => {
+            warn!("Failed to download calendar {} ({}), trying the cached copy.", url, e);
+            tokio::fs::read_to_string(&cache_path)
+                .await
+                .map_err(|_| format!("download failed ({}) and there is no cached copy", e))
+        }
+    }
+}
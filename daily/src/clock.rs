@@ -0,0 +1,86 @@
+use chrono::{DateTime, Duration, Local};
+use std::sync::Mutex;
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Local>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+/// A clock that only moves when told to, for simulations and tests.
+pub struct ManualClock {
+    now: Mutex<DateTime<Local>>,
+}
+
+impl ManualClock {
+    pub fn new(start: DateTime<Local>) -> Self {
+        ManualClock {
+            now: Mutex::new(start),
+        }
+    }
+
+    pub fn set(&self, now: DateTime<Local>) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Local> {
+        *self.now.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_config;
+    use crate::schedule::{explain, next_transition};
+    use crate::timezone::Zone;
+    use chrono::NaiveDateTime;
+
+    fn shanghai(value: &str) -> DateTime<Local> {
+        let naive = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap();
+        Zone::Named(chrono_tz::Asia::Shanghai).instant(naive).unwrap()
+    }
+
+    #[test]
+    fn steps_through_a_special_and_midnight() {
+        let text = r#"{"version": 1, "timezone": "Asia/Shanghai", "wallpapers": {
+            "days": {"friday": "friday.jpg", "saturday": "saturday.jpg", "default": "other.jpg"},
+            "specials": [{"day": "friday", "start": "22:00", "end": "23:30", "url": "late.jpg"}]
+        }}"#;
+        let config = parse_config(text).unwrap().0;
+        // 2026-10-16 is a Friday.
+        let clock = ManualClock::new(shanghai("2026-10-16 21:00"));
+        let mut seen = Vec::new();
+        while clock.now() < shanghai("2026-10-17 12:00") {
+            let decision = explain(Some(&config), None, clock.now());
+            seen.push((clock.now(), decision.url.unwrap()));
+            clock.set(next_transition(Some(&config), None, clock.now()).unwrap());
+        }
+        let expected = [
+            ("2026-10-16 21:00", "friday.jpg"),
+            ("2026-10-16 22:00", "late.jpg"),
+            ("2026-10-16 23:30", "friday.jpg"),
+            ("2026-10-17 00:00", "saturday.jpg"),
+        ];
+        let expected: Vec<_> = expected.iter().map(|(at, url)| (shanghai(at), url.to_string())).collect();
+        assert_eq!(seen, expected);
+
+        // Half-way through the window, as after waking from sleep.
+        clock.set(shanghai("2026-10-16 22:45"));
+        assert_eq!(explain(Some(&config), None, clock.now()).url.as_deref(), Some("late.jpg"));
+        clock.advance(Duration::minutes(45));
+        assert_eq!(explain(Some(&config), None, clock.now()).url.as_deref(), Some("friday.jpg"));
+    }
+}
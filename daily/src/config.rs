@@ -1,35 +1,339 @@
 use serde::{Deserialize, Serialize};
+use crate::calendars::CalendarSource;
+use crate::holidays::HolidayCalendar;
+use crate::playlist::Playlist;
+use crate::rules::Rule;
+use crate::solar::Location;
+use crate::terms::Term;
+use chrono::Weekday;
+use serde_json::{Map, Value};
 use std::collections::HashMap;
 
+pub const CONFIG_VERSION: u64 = 1;
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Config {
+    #[serde(default)]
+    pub version: u64,
     pub wallpapers: Wallpapers,
     #[serde(default = "Default::default")]
     pub special_urls: HashMap<String, String>, 
+    /// Rotations any slot can name instead of a single URL.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub playlists: HashMap<String, Playlist>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub terms: Vec<Term>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub holiday_calendar: Option<HolidayCalendar>,
+    /// iCalendar feeds imported as special periods.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub calendars: Vec<CalendarSource>,
+    /// Prioritized rules checked alongside `wallpapers`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<Rule>,
+    /// IANA name like `Asia/Shanghai` that every `HH:MM` and date is read in. Defaults
+    /// to the machine's timezone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    /// Where `sunrise`, `sunset` and the other sun times are computed for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Wallpapers {
+    #[serde(default)]
     pub days: Days,
+    #[serde(default)]
     pub dates: HashMap<String, String>,
+    #[serde(default)]
     pub specials: Vec<SpecialPeriod>,
+    /// `YYYY-MM-DD` in the first week of term; `every_n_weeks` and `week_parity` count from it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub term_start: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+/// A day's own key wins over its group (`weekdays` or `weekend`), which wins over
+/// `default`. A day nothing covers has no weekday wallpaper.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct Days { //
-    pub monday: String,
-    pub tuesday: String,
-    pub wednesday: String,
-    pub thursday: String,
-    pub friday: String,
-    pub saturday: String,
-    pub sunday: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monday: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tuesday: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wednesday: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thursday: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub friday: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub saturday: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sunday: Option<String>,
+    /// Monday to Friday.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weekdays: Option<String>,
+    /// Saturday and Sunday.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weekend: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+}
+
+impl Days {
+    /// Every key, most specific first.
+    pub fn entries(&self) -> [(&'static str, &Option<String>); 10] {
+        [
+            ("monday", &self.monday),
+            ("tuesday", &self.tuesday),
+            ("wednesday", &self.wednesday),
+            ("thursday", &self.thursday),
+            ("friday", &self.friday),
+            ("saturday", &self.saturday),
+            ("sunday", &self.sunday),
+            ("weekdays", &self.weekdays),
+            ("weekend", &self.weekend),
+            ("default", &self.default),
+        ]
+    }
+
+    pub fn entries_mut(&mut self) -> [(&'static str, &mut Option<String>); 10] {
+        [
+            ("monday", &mut self.monday),
+            ("tuesday", &mut self.tuesday),
+            ("wednesday", &mut self.wednesday),
+            ("thursday", &mut self.thursday),
+            ("friday", &mut self.friday),
+            ("saturday", &mut self.saturday),
+            ("sunday", &mut self.sunday),
+            ("weekdays", &mut self.weekdays),
+            ("weekend", &mut self.weekend),
+            ("default", &mut self.default),
+        ]
+    }
+
+    /// The key covering `weekday` and its target.
+    pub fn lookup(&self, weekday: Weekday) -> Option<(&'static str, &str)> {
+        let (day, own) = self.entries()[weekday.num_days_from_monday() as usize];
+        let (group, grouped) = match weekday {
+            Weekday::Sat | Weekday::Sun => ("weekend", &self.weekend),
+            _ => ("weekdays", &self.weekdays),
+        };
+        [(day, own), (group, grouped), ("default", &self.default)]
+            .into_iter()
+            .find_map(|(key, target)| target.as_deref().map(|target| (key, target)))
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct SpecialPeriod { //
+    /// Not needed when `repeat` is set.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub day: String,
+    /// `HH:MM` or a sun time like `sunset-30m`, which needs `location`.
     pub start: String,
     pub end: String,
+    /// Weekday the period ends on. Without it, an `end` at or before `start` means the
+    /// next day.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_day: Option<String>,
+    /// Only on these dates (any `wallpapers.dates` key form).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dates: Vec<String>,
+    /// Inclusive `YYYY-MM-DD` bounds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<String>,
+    /// Only in term weeks 1, 1 + n, 1 + 2n, ...
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub every_n_weeks: Option<u32>,
+    /// `odd` or `even` term weeks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub week_parity: Option<String>,
+    /// Term weeks like `1-8,10`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weeks: Option<String>,
+    /// `term`, `break` or `always`. With `terms` configured, specials default to `term`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub during: Option<String>,
+    /// Days beyond a single weekday: an RRULE like `FREQ=MONTHLY;BYDAY=1MO`, cron day
+    /// fields like `* * 1-5`, or `daily`, `weekdays` or `weekends`. Replaces `day` and
+    /// `end_day`; INTERVAL and COUNT count from `from`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repeat: Option<String>,
+    /// Overrides the config's `timezone` for this period.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
     pub url: String,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone)]
+pub struct MigrationNote {
+    pub path: String,
+    pub message: String,
+}
+
+/// Parses a config of any known layout, upgrading it to the current schema first.
+pub fn parse_config(text: &str) -> Result<(Config, Vec<MigrationNote>), String> {
+    let mut value: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    let notes = migrate(&mut value);
+    let config = serde_json::from_value(value).map_err(|e| e.to_string())?;
+    Ok((config, notes))
+}
+
+pub fn migrate(value: &mut Value) -> Vec<MigrationNote> {
+    let mut notes = Vec::new();
+    let Some(root) = value.as_object_mut() else {
+        return notes;
+    };
+
+    let version = root.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if version < 1 {
+        migrate_v0(root, &mut notes);
+    }
+    if version > CONFIG_VERSION {
+        notes.push(MigrationNote {
+            path: "$.version".to_string(),
+            message: format!(
+                "config version {} is newer than the supported version {}, unknown fields are ignored",
+                version, CONFIG_VERSION
+            ),
+        });
+    } else {
+        root.insert("version".to_string(), Value::from(CONFIG_VERSION));
+    }
+    notes
+}
+
+/// Version 0 is every config written before `version` existed: `special_urls` was
+/// sometimes nested in `wallpapers`, and the config creator called `specials` `periods`.
+fn migrate_v0(root: &mut Map<String, Value>, notes: &mut Vec<MigrationNote>) {
+    let mut nested_special_urls = None;
+    if let Some(Value::Object(wallpapers)) = root.get_mut("wallpapers") {
+        if let Some(periods) = wallpapers.remove("periods") {
+            if wallpapers.contains_key("specials") {
+                notes.push(MigrationNote {
+                    path: "$.wallpapers.periods".to_string(),
+                    message: "dropped in favour of $.wallpapers.specials".to_string(),
+                });
+            } else {
+                wallpapers.insert("specials".to_string(), periods);
+                notes.push(MigrationNote {
+                    path: "$.wallpapers.periods".to_string(),
+                    message: "renamed to $.wallpapers.specials".to_string(),
+                });
+            }
+        }
+        nested_special_urls = wallpapers.remove("special_urls");
+    }
+
+    if let Some(Value::Object(nested)) = nested_special_urls {
+        let special_urls = root
+            .entry("special_urls")
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Value::Object(special_urls) = special_urls {
+            for (id, url) in nested {
+                if special_urls.contains_key(&id) {
+                    notes.push(MigrationNote {
+                        path: format!("$.wallpapers.special_urls.{}", id),
+                        message: "ignored, $.special_urls already defines it".to_string(),
+                    });
+                } else {
+                    special_urls.insert(id, url);
+                }
+            }
+            notes.push(MigrationNote {
+                path: "$.wallpapers.special_urls".to_string(),
+                message: "moved to $.special_urls".to_string(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn paths(notes: &[MigrationNote]) -> Vec<&str> {
+        notes.iter().map(|note| note.path.as_str()).collect()
+    }
+
+    #[test]
+    fn moves_nested_special_urls_to_the_top() {
+        let mut value = json!({
+            "wallpapers": {"days": {}, "special_urls": {"a": "https://a.example/", "b": "https://nested.example/"}},
+            "special_urls": {"b": "https://top.example/"}
+        });
+        let notes = migrate(&mut value);
+        assert_eq!(paths(&notes), ["$.wallpapers.special_urls.b", "$.wallpapers.special_urls"]);
+        assert_eq!(
+            value,
+            json!({
+                "version": CONFIG_VERSION,
+                "wallpapers": {"days": {}},
+                "special_urls": {"a": "https://a.example/", "b": "https://top.example/"}
+            })
+        );
+    }
+
+    #[test]
+    fn renames_periods_to_specials() {
+        let period = json!({"day": "friday", "start": "18:00", "end": "20:00", "url": ""});
+        let mut value = json!({"wallpapers": {"periods": [period.clone()]}});
+        let notes = migrate(&mut value);
+        assert_eq!(paths(&notes), ["$.wallpapers.periods"]);
+        assert_eq!(value["wallpapers"], json!({"specials": [period.clone()]}));
+
+        // When both exist, specials wins.
+        let mut value = json!({"wallpapers": {"periods": [period], "specials": []}});
+        let notes = migrate(&mut value);
+        assert_eq!(notes[0].message, "dropped in favour of $.wallpapers.specials");
+        assert_eq!(value["wallpapers"], json!({"specials": []}));
+
+        let (config, _) = parse_config(r#"{"wallpapers": {"periods": [{"day": "friday", "start": "18:00", "end": "20:00", "url": "x"}]}}"#)
+            .unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.wallpapers.specials[0].url, "x");
+    }
+
+    fn days(value: Value) -> Days {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn a_day_beats_its_group_which_beats_default() {
+        let days = days(json!({"friday": "f.jpg", "weekdays": "w.jpg", "weekend": "", "default": "d.jpg"}));
+        assert_eq!(days.lookup(Weekday::Fri), Some(("friday", "f.jpg")));
+        assert_eq!(days.lookup(Weekday::Mon), Some(("weekdays", "w.jpg")));
+        // An empty group still covers its days, clearing the wallpaper.
+        assert_eq!(days.lookup(Weekday::Sat), Some(("weekend", "")));
+
+        let days = self::days(json!({"sunday": "s.jpg", "weekdays": "w.jpg", "default": "d.jpg"}));
+        assert_eq!(days.lookup(Weekday::Sun), Some(("sunday", "s.jpg")));
+        assert_eq!(days.lookup(Weekday::Sat), Some(("default", "d.jpg")));
+        assert_eq!(days.lookup(Weekday::Thu), Some(("weekdays", "w.jpg")));
+    }
+
+    #[test]
+    fn uncovered_days_have_no_entry() {
+        let days = days(json!({"weekend": "e.jpg", "tuesday": "t.jpg"}));
+        assert_eq!(days.lookup(Weekday::Mon), None);
+        assert_eq!(days.lookup(Weekday::Tue), Some(("tuesday", "t.jpg")));
+        assert_eq!(days.lookup(Weekday::Sun), Some(("weekend", "e.jpg")));
+    }
+
+    #[test]
+    fn newer_versions_are_kept_and_reported() {
+        let mut value = json!({"version": CONFIG_VERSION + 1, "wallpapers": {"periods": []}});
+        let notes = migrate(&mut value);
+        assert_eq!(paths(&notes), ["$.version"]);
+        // Nothing is rewritten, not even what looks like an old layout.
+        assert_eq!(value, json!({"version": CONFIG_VERSION + 1, "wallpapers": {"periods": []}}));
+
+        let mut value = json!({"version": CONFIG_VERSION, "wallpapers": {}});
+        assert!(migrate(&mut value).is_empty());
+    }
+}
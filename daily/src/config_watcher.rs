@@ -0,0 +1,100 @@
+use crate::file_manager::{load_local_config, reload_override, CONFIG_FILE_NAME, OVERRIDE_FILE_NAME};
+use crate::state::AppState;
+use log::{debug, error, info, warn};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::sleep;
+
+// Editors and our own downloader write files in several steps; let them settle first.
+const SETTLE_DELAY: Duration = Duration::from_millis(500);
+
+pub async fn watch_loop(state: Arc<AppState>) {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher = match notify::recommended_watcher(move |res: notify::Result<Event>| {
+        tx.send(res).ok();
+    }) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            error!("Failed to create config watcher: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = watcher.watch(&state.app_data_dir, RecursiveMode::NonRecursive) {
+        error!("Failed to watch {}: {}", state.app_data_dir.display(), e);
+        return;
+    }
+    info!("Watching {} for local config changes", state.app_data_dir.display());
+
+    while let Some(res) = rx.recv().await {
+        let mut changes = Changes::default();
+        changes.record(res);
+        if !changes.any() {
+            continue;
+        }
+
+        sleep(SETTLE_DELAY).await;
+        while let Ok(res) = rx.try_recv() {
+            changes.record(res);
+        }
+        // A downloaded config was applied when it was written; reloading it would only
+        // fetch the calendars again.
+        if changes.config && is_own_write(&state).await {
+            debug!("{} was written by the config download, not reloading.", CONFIG_FILE_NAME);
+            changes.config = false;
+        }
+        // Reloading config.json re-applies the override as well.
+        if changes.config {
+            info!("Local config changed, reloading.");
+            if let Err(e) = load_local_config(&state).await {
+                error!("Failed to reload local config: {}", e);
+            }
+        } else if changes.overrides {
+            info!("Local overrides changed, reloading.");
+            if let Err(e) = reload_override(&state).await {
+                error!("Failed to reload overrides: {}", e);
+            }
+        }
+    }
+}
+
+async fn is_own_write(state: &AppState) -> bool {
+    let Ok(text) = tokio::fs::read_to_string(state.app_data_dir.join(CONFIG_FILE_NAME)).await else {
+        return false;
+    };
+    state.written_config.lock().await.as_deref() == Some(sha256::digest(&text).as_str())
+}
+
+#[derive(Default)]
+struct Changes {
+    config: bool,
+    overrides: bool,
+}
+
+impl Changes {
+    fn record(&mut self, res: notify::Result<Event>) {
+        let event = match res {
+            Ok(event) => event,
+            Err(e) => {
+                warn!("Config watcher error: {}", e);
+                return;
+            }
+        };
+        if matches!(event.kind, EventKind::Access(_)) {
+            return;
+        }
+        for name in event.paths.iter().filter_map(|path| path.file_name()?.to_str()) {
+            match name {
+                CONFIG_FILE_NAME => self.config = true,
+                OVERRIDE_FILE_NAME => self.overrides = true,
+                _ => continue,
+            }
+            debug!("Config file event: {:?}", event);
+        }
+    }
+
+    fn any(&self) -> bool {
+        self.config || self.overrides
+    }
+}
@@ -0,0 +1,158 @@
+use crate::terms::{named_span, Term};
+use chrono::{Datelike, NaiveDate};
+use std::cmp::Ordering;
+
+/// A key of `wallpapers.dates`:
+/// - `MM-DD`, every year;
+/// - `YYYY-MM-DD`, one specific day;
+/// - `MM-DD..MM-DD`, every year, and may cross New Year (`12-20..01-05`);
+/// - `YYYY-MM-DD..YYYY-MM-DD`, one specific span;
+/// - `term:<name>` or `break:<name>`, the dates of an entry in `terms`.
+///
+/// Ranges include both ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateKey {
+    Yearly { start: (u32, u32), end: (u32, u32) },
+    Fixed { start: NaiveDate, end: NaiveDate },
+}
+
+impl DateKey {
+    pub fn parse(key: &str) -> Result<DateKey, String> {
+        let (start, end) = match key.split_once("..") {
+            Some((start, end)) => (start.trim(), end.trim()),
+            None => (key.trim(), key.trim()),
+        };
+        match (parse_point(start)?, parse_point(end)?) {
+            (Point::Yearly(start), Point::Yearly(end)) => Ok(DateKey::Yearly { start, end }),
+            (Point::Fixed(start), Point::Fixed(end)) if start <= end => Ok(DateKey::Fixed { start, end }),
+            (Point::Fixed(_), Point::Fixed(_)) => Err(format!("range '{}' ends before it starts", key)),
+            _ => Err(format!(
+                "range '{}' mixes MM-DD and YYYY-MM-DD, use the same form on both sides",
+                key
+            )),
+        }
+    }
+
+    pub fn parse_with_terms(key: &str, terms: &[Term]) -> Result<DateKey, String> {
+        match named_span(terms, key) {
+            Some(span) => span.map(|(start, end)| DateKey::Fixed { start, end }),
+            None => DateKey::parse(key),
+        }
+    }
+
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        match *self {
+            DateKey::Fixed { start, end } => start <= date && date <= end,
+            DateKey::Yearly { start, end } => {
+                let day = (date.month(), date.day());
+                if start <= end {
+                    start <= day && day <= end
+                } else {
+                    day >= start || day <= end
+                }
+            }
+        }
+    }
+
+    /// How many days the key covers; fewer days means more specific.
+    pub fn span_days(&self) -> i64 {
+        match *self {
+            DateKey::Fixed { start, end } => (end - start).num_days() + 1,
+            DateKey::Yearly { start, end } => {
+                // A leap year, so that 02-29 is a real day.
+                let from = yearly_date(2000, start);
+                let to = if start <= end { yearly_date(2000, end) } else { yearly_date(2001, end) };
+                (to - from).num_days() + 1
+            }
+        }
+    }
+
+    /// Precedence for overlapping keys: the shorter span wins, then a fixed year wins
+    /// over a yearly key. Equal keys fall back to comparing the raw key text.
+    pub fn specificity_cmp(&self, other: &DateKey) -> Ordering {
+        self.span_days()
+            .cmp(&other.span_days())
+            .then_with(|| matches!(other, DateKey::Fixed { .. }).cmp(&matches!(self, DateKey::Fixed { .. })))
+    }
+}
+
+enum Point {
+    Yearly((u32, u32)),
+    Fixed(NaiveDate),
+}
+
+fn parse_point(value: &str) -> Result<Point, String> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        && value.len() == 10
+    {
+        return Ok(Point::Fixed(date));
+    }
+    // Parse against a leap year so that 02-29 is accepted.
+    match NaiveDate::parse_from_str(&format!("2000-{}", value), "%Y-%m-%d") {
+        Ok(date) if value.len() == 5 => Ok(Point::Yearly((date.month(), date.day()))),
+        _ => Err(format!("'{}' is not a valid MM-DD or YYYY-MM-DD date", value)),
+    }
+}
+
+fn yearly_date(year: i32, (month, day): (u32, u32)) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap_or(NaiveDate::MIN)
+}
+
+/// Every date key that covers `date`, most specific first. Keys that fail to parse are
+/// ignored here; the validator reports them.
+pub fn matching_keys<'a>(
+    keys: impl Iterator<Item = &'a String>,
+    date: NaiveDate,
+    terms: &[Term],
+) -> Vec<&'a String> {
+    let mut matches: Vec<(&String, DateKey)> = keys
+        .filter_map(|key| DateKey::parse_with_terms(key, terms).ok().map(|parsed| (key, parsed)))
+        .filter(|(_, parsed)| parsed.contains(date))
+        .collect();
+    matches.sort_by(|(a, a_key), (b, b_key)| a_key.specificity_cmp(b_key).then_with(|| a.cmp(b)));
+    matches.into_iter().map(|(key, _)| key).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn yearly_range_crosses_new_year() {
+        let key = DateKey::parse("12-20..01-05").unwrap();
+        assert!(key.contains(date("2026-12-20")));
+        assert!(key.contains(date("2027-01-05")));
+        assert!(!key.contains(date("2027-01-06")));
+        assert!(!key.contains(date("2026-12-19")));
+        assert_eq!(key.span_days(), 17);
+    }
+
+    #[test]
+    fn fixed_range_includes_both_ends() {
+        let key = DateKey::parse("2026-10-01..2026-10-07").unwrap();
+        assert!(key.contains(date("2026-10-01")));
+        assert!(key.contains(date("2026-10-07")));
+        assert!(!key.contains(date("2026-10-08")));
+        assert!(!key.contains(date("2025-10-03")));
+    }
+
+    #[test]
+    fn rejects_bad_ranges() {
+        assert!(DateKey::parse("2026-10-07..2026-10-01").is_err());
+        assert!(DateKey::parse("10-01..2026-10-07").is_err());
+        assert!(DateKey::parse("13-01").is_err());
+        assert!(DateKey::parse("2026-2-1").is_err());
+        assert!(DateKey::parse("02-29").is_ok());
+    }
+
+    #[test]
+    fn most_specific_key_first() {
+        let keys = ["10-01..10-07", "2026-10-01..2026-10-07", "2026-10-03", "09-01..10-31"].map(String::from);
+        let matched = matching_keys(keys.iter(), date("2026-10-03"), &[]);
+        assert_eq!(matched, ["2026-10-03", "2026-10-01..2026-10-07", "10-01..10-07", "09-01..10-31"]);
+    }
+}
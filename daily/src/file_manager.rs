@@ -1,6 +1,11 @@
 // daily/src/file_manager.rs
+use crate::calendars;
 use crate::config::Config;
+use crate::holidays;
+use crate::overrides::{self, ConfigOverride};
+use crate::sources;
 use crate::state::AppState;
+use crate::validate::{has_errors, parse_and_validate, validate_config};
 use log::{debug, error, info, warn};
 use reqwest::Client;
 use std::path::{Path, PathBuf};
@@ -10,6 +15,8 @@ use tokio::fs;
 use tokio::time::sleep;
 
 const APP_DIR_NAME: &str = "DailyWallpaper";
+pub const CONFIG_FILE_NAME: &str = "config.json";
+pub const OVERRIDE_FILE_NAME: &str = "override.json";
 
 
 pub fn init_app_data_dir() -> Result<PathBuf, std::io::Error> {
@@ -53,7 +60,7 @@ pub async fn download_config(
     state: Arc<AppState>,
     config_url: &str,
 ) -> Result<(), String> {
-    let config_path = state.app_data_dir.join("config.json");
+    let config_path = state.app_data_dir.join(CONFIG_FILE_NAME);
     info!("Downloading new config from {}", config_url);
 
     let resp = client.get(config_url).send().await.map_err(|e| e.to_string())?;
@@ -62,15 +69,96 @@ pub async fn download_config(
     }
     
     let config_text = resp.text().await.map_err(|e| e.to_string())?;
-    let new_config: Config = serde_json::from_str(&config_text).map_err(|e| e.to_string())?;
+    let (new_config, diagnostics) = parse_and_validate(&config_text, state.clock.now());
+    for diagnostic in &diagnostics {
+        warn!("Config {}", diagnostic);
+    }
+    let new_config = match new_config {
+        Some(config) if !has_errors(&diagnostics) => config,
+        _ => return Err("Downloaded config failed validation, keeping the current one".to_string()),
+    };
+    *state.written_config.lock().await = Some(sha256::digest(&config_text));
     fs::write(&config_path, config_text).await.map_err(|e| e.to_string())?;
-    let mut config_lock = state.config.lock().await;
-    *config_lock = Some(new_config);
-    state.wallpaper_notify.notify_one();
+    apply_config(&state, new_config).await;
     info!("Config updated successfully.");
     Ok(())
 }
 
+pub async fn load_local_config(state: &AppState) -> Result<(), String> {
+    let config_path = state.app_data_dir.join(CONFIG_FILE_NAME);
+    let config_text = fs::read_to_string(&config_path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", config_path.display(), e))?;
+    let (local_config, diagnostics) = parse_and_validate(&config_text, state.clock.now());
+    for diagnostic in &diagnostics {
+        warn!("Local config {}", diagnostic);
+    }
+    let local_config = match local_config {
+        Some(config) if !has_errors(&diagnostics) => config,
+        _ => return Err("Local config failed validation".to_string()),
+    };
+    apply_config(state, local_config).await;
+    info!("Loaded local config successfully.");
+    Ok(())
+}
+
+/// Re-merges `override.json` over the last accepted remote config.
+pub async fn reload_override(state: &AppState) -> Result<(), String> {
+    let remote = state.remote_config.lock().await.clone();
+    let remote = remote.ok_or("No remote config loaded yet")?;
+    apply_config(state, remote).await;
+    Ok(())
+}
+
+/// Returns `Ok(None)` when there is no `override.json`.
+pub async fn load_override(app_data_dir: &Path) -> Result<Option<ConfigOverride>, String> {
+    let path = app_data_dir.join(OVERRIDE_FILE_NAME);
+    match fs::read_to_string(&path).await {
+        Ok(text) => overrides::parse_override(&text)
+            .map(Some)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+/// Installs a validated remote config, layering `override.json` on top. A broken
+/// override is ignored rather than taking the remote config down with it.
+async fn apply_config(state: &AppState, remote: Config) {
+    let mut layer = match load_override(&state.app_data_dir).await {
+        Ok(layer) => layer,
+        Err(e) => {
+            error!("{}, ignoring overrides.", e);
+            None
+        }
+    };
+    let (mut merged, mut provenance) = overrides::merge(&remote, layer.as_ref());
+    if layer.is_some() {
+        let diagnostics = validate_config(&merged, state.clock.now());
+        for diagnostic in &diagnostics {
+            warn!("Merged config {}", diagnostic);
+        }
+        if has_errors(&diagnostics) {
+            error!("Config with overrides failed validation, ignoring overrides.");
+            (merged, provenance) = overrides::merge(&remote, None);
+            layer = None;
+        } else {
+            info!("Applied overrides from {}", OVERRIDE_FILE_NAME);
+        }
+    }
+    if let Err(e) = holidays::load(&mut merged, &state.app_data_dir) {
+        error!("{}, using the rest of the holiday calendar.", e);
+    }
+    for e in calendars::load(&mut merged, &state.app_data_dir, &Client::new()).await {
+        error!("{}", e);
+    }
+    overrides::block_events(&mut merged, &remote, layer.as_ref(), &mut provenance);
+    *state.remote_config.lock().await = Some(remote);
+    *state.config.lock().await = Some(merged);
+    *state.provenance.lock().await = provenance;
+    state.wallpaper_notify.notify_one();
+}
+
 pub async fn cleanup_loop(state: Arc<AppState>, config_url: &str) {
     let client = Client::new();
     loop {
@@ -79,24 +167,16 @@ pub async fn cleanup_loop(state: Arc<AppState>, config_url: &str) {
             error!("Failed to download new config: {}", e);
             if state.config.lock().await.is_none() {
                 warn!("No config loaded. Attempting to load from local file.");
-                let config_path = state.app_data_dir.join("config.json");
-                if let Ok(config_text) = fs::read_to_string(config_path).await {
-                    if let Ok(local_config) = serde_json::from_str::<Config>(&config_text) {
-                        *state.config.lock().await = Some(local_config);
-                        info!("Loaded local config successfully.");
-                    } else {
-                        error!("Failed to parse local config file.");
-                    }
-                } else {
-                    error!("No local config file found. Waiting for next cycle.");
+                if let Err(e) = load_local_config(&state).await {
+                    error!("{}. Waiting for next cycle.", e);
                 }
             }
         }
         let current_url = state.current_wallpaper_url.lock().await.clone();
-        let current_filename = if !current_url.is_empty() && !current_url.starts_with("special") {
-        wallpaper_url_to_path(&state.app_data_dir, &current_url)
-            .file_name()
-            .map(|s| s.to_os_string())
+        // Cached files are named after the URL's hash, whatever follows it.
+        let is_web_page = sources::is_web_page(state.config.lock().await.as_ref(), &current_url);
+        let current_hash = if !current_url.is_empty() && !is_web_page {
+            Some(sha256::digest(&current_url))
         } else {
             None
         };
@@ -105,22 +185,20 @@ pub async fn cleanup_loop(state: Arc<AppState>, config_url: &str) {
             while let Ok(Some(entry)) = entries.next_entry().await {
                 let path = entry.path();
                 if path.is_file() {
-                    if let Some(ref current) = current_filename {
-                        if entry.file_name() == *current {
-                            debug!("Skipping cleanup for active wallpaper: {:?}", entry.file_name());
-                            continue;
-                        }
+                    if let Some(ref current) = current_hash
+                        && entry.file_name().to_string_lossy().starts_with(current.as_str())
+                    {
+                        debug!("Skipping cleanup for active wallpaper: {:?}", entry.file_name());
+                        continue;
                     }
-                    if let Ok(metadata) = entry.metadata().await {
-                        if let Ok(modified) = metadata.modified() {
-                            if let Ok(duration) = modified.elapsed() {
-                                if duration > Duration::from_secs(60 * 60 * 48) { 
-                                    info!("Cleaning up old wallpaper: {:?}", path.file_name());
-                                    if let Err(e) = fs::remove_file(path).await {
-                                        error!("Failed to delete old wallpaper: {}", e);
-                                    }
-                                }
-                            }
+                    if let Ok(metadata) = entry.metadata().await
+                        && let Ok(modified) = metadata.modified()
+                        && let Ok(duration) = modified.elapsed()
+                        && duration > Duration::from_secs(60 * 60 * 48)
+                    {
+                        info!("Cleaning up old wallpaper: {:?}", path.file_name());
+                        if let Err(e) = fs::remove_file(path).await {
+                            error!("Failed to delete old wallpaper: {}", e);
                         }
                     }
                 }
@@ -141,4 +219,10 @@ pub fn wallpaper_url_to_path(app_data_dir: &Path, url: &str) -> PathBuf {
         .unwrap_or("jpg");
     
     app_data_dir.join("wallpapers").join(format!("{}.{}", hash, extension))
+}
+
+/// Where a `color:`, `gradient:` or `pattern:` wallpaper drawn at `width` x `height` is kept.
+pub fn generated_wallpaper_path(app_data_dir: &Path, url: &str, width: u32, height: u32) -> PathBuf {
+    let hash = sha256::digest(url);
+    app_data_dir.join("wallpapers").join(format!("{}.{}x{}.bmp", hash, width, height))
 }
\ No newline at end of file
@@ -0,0 +1,322 @@
+use chrono::{Datelike, NaiveDate};
+use std::collections::HashMap;
+
+/// Used when the backend cannot tell the screen size.
+pub const DEFAULT_SIZE: (u32, u32) = (1920, 1080);
+const SCHEMES: [&str; 3] = ["color:", "gradient:", "pattern:"];
+
+type Rgb = [u8; 3];
+
+/// A wallpaper drawn locally instead of downloaded:
+/// - `color:#1a1a1a`
+/// - `gradient:#000,#333@45`, any number of colors, the angle in CSS degrees (default 180, top to bottom)
+/// - `pattern:noise`, `pattern:checker` and `pattern:stripes`, with `?colors=#000,#333`,
+///   `seed=42` or `seed=date` (noise), `scale=` (noise), `size=` (checker), `width=` and
+///   `angle=` (stripes)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Generated {
+    Color(Rgb),
+    Gradient { stops: Vec<Rgb>, angle: f64 },
+    Noise { colors: [Rgb; 2], seed: u64, scale: u32 },
+    Checker { colors: [Rgb; 2], size: u32 },
+    Stripes { colors: [Rgb; 2], width: u32, angle: f64 },
+}
+
+pub fn is_generated(target: &str) -> bool {
+    SCHEMES.iter().any(|scheme| target.starts_with(scheme))
+}
+
+/// Replaces `seed=date` with the day's number, so each day is a different target and
+/// gets its own image.
+pub fn pin_date(target: &str, date: NaiveDate) -> String {
+    if !is_generated(target) {
+        return target.to_string();
+    }
+    target.replace("seed=date", &format!("seed={}", date_seed(date)))
+}
+
+fn date_seed(date: NaiveDate) -> u64 {
+    (date.year() as u64) * 10_000 + date.month() as u64 * 100 + date.day() as u64
+}
+
+impl Generated {
+    /// `date` is what `seed=date` stands for.
+    pub fn parse(target: &str, date: NaiveDate) -> Result<Generated, String> {
+        if let Some(color) = target.strip_prefix("color:") {
+            return parse_color(color).map(Generated::Color);
+        }
+        if let Some(spec) = target.strip_prefix("gradient:") {
+            let (colors, angle) = match spec.split_once('@') {
+                Some((colors, angle)) => (colors, parse_number(angle, "angle")?),
+                None => (spec, 180.0),
+            };
+            let stops = colors.split(',').map(parse_color).collect::<Result<Vec<_>, _>>()?;
+            if stops.len() < 2 {
+                return Err(format!("'{}' needs at least two colors", target));
+            }
+            return Ok(Generated::Gradient { stops, angle });
+        }
+        let Some(spec) = target.strip_prefix("pattern:") else {
+            return Err(format!("'{}' is not a color:, gradient: or pattern: wallpaper", target));
+        };
+        let (kind, query) = spec.split_once('?').unwrap_or((spec, ""));
+        let mut params: HashMap<&str, &str> = HashMap::new();
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("'{}' in '{}' is not key=value", pair, target))?;
+            params.insert(key, value);
+        }
+        let colors = match params.remove("colors") {
+            Some(colors) => match colors.split(',').map(parse_color).collect::<Result<Vec<_>, _>>()?[..] {
+                [first, second] => [first, second],
+                _ => return Err(format!("colors in '{}' must be exactly two colors", target)),
+            },
+            None => [[0x1a, 0x1a, 0x1a], [0x4a, 0x4a, 0x4a]],
+        };
+        let generated = match kind {
+            "noise" => {
+                let scale = take_size(&mut params, "scale", 96)?;
+                let seed = match params.remove("seed") {
+                    Some("date") => date_seed(date),
+                    Some(seed) => seed.parse().map_err(|_| format!("seed '{}' is not a number or 'date'", seed))?,
+                    None => 0,
+                };
+                Generated::Noise { colors, seed, scale }
+            }
+            "checker" => Generated::Checker {
+                colors,
+                size: take_size(&mut params, "size", 64)?,
+            },
+            "stripes" => {
+                let width = take_size(&mut params, "width", 40)?;
+                let angle = match params.remove("angle") {
+                    Some(angle) => parse_number(angle, "angle")?,
+                    None => 45.0,
+                };
+                Generated::Stripes { colors, width, angle }
+            }
+            _ => return Err(format!("unknown pattern '{}' (expected noise, checker or stripes)", kind)),
+        };
+        if let Some(key) = params.keys().next() {
+            return Err(format!("{} does not take '{}'", kind, key));
+        }
+        Ok(generated)
+    }
+
+    /// The color of pixel (`x`, `y`) on a `width` x `height` screen.
+    pub fn pixel(&self, x: u32, y: u32, width: u32, height: u32) -> Rgb {
+        let (fx, fy) = (x as f64 + 0.5, y as f64 + 0.5);
+        match self {
+            Generated::Color(color) => *color,
+            Generated::Gradient { stops, angle } => {
+                // As in CSS: 0deg points up, 90deg right, and the line is long enough
+                // for the corners to get the first and last colors.
+                let (dx, dy) = (angle.to_radians().sin(), -angle.to_radians().cos());
+                let length = (width as f64 * dx).abs() + (height as f64 * dy).abs();
+                let along = (fx - width as f64 / 2.0) * dx + (fy - height as f64 / 2.0) * dy;
+                let t = (along / length + 0.5).clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+                let i = (t.floor() as usize).min(stops.len() - 2);
+                mix(stops[i], stops[i + 1], t - i as f64)
+            }
+            Generated::Noise { colors, seed, scale } => {
+                // Three octaves of value noise.
+                let mut value = 0.0;
+                let mut amplitude = 0.5;
+                let mut cell = *scale as f64;
+                for octave in 0..3 {
+                    value += amplitude * value_noise(fx / cell, fy / cell, seed.wrapping_add(octave));
+                    amplitude /= 2.0;
+                    cell /= 2.0;
+                }
+                mix(colors[0], colors[1], value / 0.875)
+            }
+            Generated::Checker { colors, size } => colors[((x / size + y / size) % 2) as usize],
+            Generated::Stripes { colors, width, angle } => {
+                let (dx, dy) = (angle.to_radians().cos(), angle.to_radians().sin());
+                let along = fx * dx + fy * dy;
+                colors[(along / *width as f64).floor().rem_euclid(2.0) as usize]
+            }
+        }
+    }
+
+    /// The image as a 24-bit BMP, which every backend accepts and needs no compressor.
+    pub fn render_bmp(&self, width: u32, height: u32) -> Vec<u8> {
+        let row_len = (width as usize * 3).div_ceil(4) * 4;
+        let data_len = row_len * height as usize;
+        let mut bmp = Vec::with_capacity(54 + data_len);
+        // BITMAPFILEHEADER
+        bmp.extend_from_slice(b"BM");
+        bmp.extend_from_slice(&(54 + data_len as u32).to_le_bytes());
+        bmp.extend_from_slice(&[0; 4]);
+        bmp.extend_from_slice(&54u32.to_le_bytes());
+        // BITMAPINFOHEADER, uncompressed
+        bmp.extend_from_slice(&40u32.to_le_bytes());
+        bmp.extend_from_slice(&(width as i32).to_le_bytes());
+        bmp.extend_from_slice(&(height as i32).to_le_bytes());
+        bmp.extend_from_slice(&1u16.to_le_bytes());
+        bmp.extend_from_slice(&24u16.to_le_bytes());
+        bmp.extend_from_slice(&0u32.to_le_bytes());
+        bmp.extend_from_slice(&(data_len as u32).to_le_bytes());
+        bmp.extend_from_slice(&2835u32.to_le_bytes());
+        bmp.extend_from_slice(&2835u32.to_le_bytes());
+        bmp.extend_from_slice(&[0; 8]);
+        // Rows go bottom to top, pixels as BGR.
+        for y in (0..height).rev() {
+            let start = bmp.len();
+            for x in 0..width {
+                let [r, g, b] = self.pixel(x, y, width, height);
+                bmp.extend_from_slice(&[b, g, r]);
+            }
+            bmp.resize(start + row_len, 0);
+        }
+        bmp
+    }
+}
+
+/// `#rgb` or `#rrggbb`.
+fn parse_color(value: &str) -> Result<Rgb, String> {
+    let invalid = || format!("'{}' is not a #rgb or #rrggbb color", value);
+    let hex = value.trim().strip_prefix('#').ok_or_else(invalid)?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    let channel = |s: &str| u8::from_str_radix(s, 16).map_err(|_| invalid());
+    match hex.len() {
+        3 => {
+            let digits: Vec<String> = hex.chars().map(|c| format!("{}{}", c, c)).collect();
+            Ok([channel(&digits[0])?, channel(&digits[1])?, channel(&digits[2])?])
+        }
+        6 => Ok([channel(&hex[0..2])?, channel(&hex[2..4])?, channel(&hex[4..6])?]),
+        _ => Err(invalid()),
+    }
+}
+
+fn take_size(params: &mut HashMap<&str, &str>, key: &str, default: u32) -> Result<u32, String> {
+    match params.remove(key) {
+        Some(value) => value
+            .parse::<u32>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| format!("{} '{}' is not a positive whole number", key, value)),
+        None => Ok(default),
+    }
+}
+
+fn parse_number(value: &str, name: &str) -> Result<f64, String> {
+    value
+        .trim()
+        .trim_end_matches("deg")
+        .parse::<f64>()
+        .ok()
+        .filter(|n| n.is_finite())
+        .ok_or_else(|| format!("{} '{}' is not a number", name, value))
+}
+
+fn mix(from: Rgb, to: Rgb, t: f64) -> Rgb {
+    let t = t.clamp(0.0, 1.0);
+    let channel = |i: usize| (from[i] as f64 + (to[i] as f64 - from[i] as f64) * t).round() as u8;
+    [channel(0), channel(1), channel(2)]
+}
+
+/// Smoothly interpolated random values on a unit grid, in 0..1.
+fn value_noise(x: f64, y: f64, seed: u64) -> f64 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (tx, ty) = (smoothstep(x - x0), smoothstep(y - y0));
+    let corner = |dx: f64, dy: f64| lattice((x0 + dx) as i64, (y0 + dy) as i64, seed);
+    let top = corner(0.0, 0.0) + (corner(1.0, 0.0) - corner(0.0, 0.0)) * tx;
+    let bottom = corner(0.0, 1.0) + (corner(1.0, 1.0) - corner(0.0, 1.0)) * tx;
+    top + (bottom - top) * ty
+}
+
+fn smoothstep(t: f64) -> f64 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// A fixed random value in 0..1 for a grid point (SplitMix64).
+fn lattice(x: i64, y: i64, seed: u64) -> f64 {
+    let mut z = seed
+        .wrapping_add((x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+        .wrapping_add((y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, 18).unwrap()
+    }
+
+    #[test]
+    fn parses_colors_and_gradients() {
+        assert_eq!(Generated::parse("color:#abc", date()), Ok(Generated::Color([0xaa, 0xbb, 0xcc])));
+        assert_eq!(
+            Generated::parse("gradient:#000,#ffffff@90deg", date()),
+            Ok(Generated::Gradient {
+                stops: vec![[0, 0, 0], [255, 255, 255]],
+                angle: 90.0
+            })
+        );
+        assert!(Generated::parse("color:abc", date()).is_err());
+        assert!(Generated::parse("color:#abcd", date()).is_err());
+        assert!(Generated::parse("gradient:#000", date()).is_err());
+    }
+
+    #[test]
+    fn parses_patterns() {
+        assert_eq!(
+            Generated::parse("pattern:noise?seed=date&scale=32", date()),
+            Ok(Generated::Noise {
+                colors: [[0x1a, 0x1a, 0x1a], [0x4a, 0x4a, 0x4a]],
+                seed: 20261018,
+                scale: 32
+            })
+        );
+        assert_eq!(
+            Generated::parse("pattern:checker?colors=#000,#fff&size=8", date()),
+            Ok(Generated::Checker {
+                colors: [[0, 0, 0], [255, 255, 255]],
+                size: 8
+            })
+        );
+        assert!(Generated::parse("pattern:checker?size=0", date()).is_err());
+        assert!(Generated::parse("pattern:checker?seed=1", date()).is_err());
+        assert!(Generated::parse("pattern:waves", date()).is_err());
+        assert!(Generated::parse("pattern:noise?colors=#000", date()).is_err());
+    }
+
+    #[test]
+    fn pins_date_seeds() {
+        assert_eq!(pin_date("pattern:noise?seed=date", date()), "pattern:noise?seed=20261018");
+        assert_eq!(pin_date("https://example.com/seed=date.jpg", date()), "https://example.com/seed=date.jpg");
+    }
+
+    #[test]
+    fn gradient_runs_top_to_bottom() {
+        let gradient = Generated::parse("gradient:#000,#fff", date()).unwrap();
+        assert_eq!(gradient.pixel(5, 0, 10, 100), [1, 1, 1]);
+        assert_eq!(gradient.pixel(5, 99, 10, 100), [254, 254, 254]);
+    }
+
+    #[test]
+    fn renders_a_padded_bottom_up_bmp() {
+        let checker = Generated::parse("pattern:checker?colors=#ff0000,#0000ff&size=1", date()).unwrap();
+        let bmp = checker.render_bmp(3, 2);
+        // Rows of 3 pixels are padded from 9 to 12 bytes.
+        assert_eq!(bmp.len(), 54 + 12 * 2);
+        assert_eq!(&bmp[..2], b"BM");
+        assert_eq!(u32::from_le_bytes(bmp[2..6].try_into().unwrap()), 78);
+        assert_eq!(i32::from_le_bytes(bmp[18..22].try_into().unwrap()), 3);
+        assert_eq!(i32::from_le_bytes(bmp[22..26].try_into().unwrap()), 2);
+        // The first row stored is the bottom one (y = 1), which starts blue, in BGR.
+        assert_eq!(&bmp[54..57], &[255, 0, 0]);
+        assert_eq!(&bmp[57..60], &[0, 0, 255]);
+        assert_eq!(&bmp[63..66], &[0, 0, 0]);
+        assert_eq!(&bmp[66..69], &[0, 0, 255]);
+    }
+}
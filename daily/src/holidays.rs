@@ -0,0 +1,275 @@
+use crate::config::Config;
+use crate::dates::DateKey;
+use crate::ics;
+use crate::schedule::{weekday_from_str, weekday_name};
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// Official holidays and make-up workdays (调休) that override the normal weekday.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct HolidayCalendar {
+    /// A calendar shipped with the app, e.g. `cn`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bundled: Option<String>,
+    /// A `.json` or `.ics` file, relative to the app data directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entries: Vec<HolidayEntry>,
+    /// Which weekday's rules a make-up workday follows. Defaults to `monday`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workday_as: Option<String>,
+    /// Which weekday's wallpaper a holiday shows. Defaults to `sunday`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub holiday_as: Option<String>,
+    /// `entries`, then those read from `file`, then every year of the `bundled`
+    /// calendar, with their dates parsed. Filled in when the config is loaded.
+    #[serde(skip)]
+    pub resolved: Vec<(DateKey, HolidayEntry)>,
+}
+
+impl HolidayCalendar {
+    /// Parses the dates of `entries` and `sources` once, dropping invalid ones, which
+    /// validation reports.
+    fn resolve(&mut self, sources: Vec<HolidayEntry>) {
+        self.resolved = self
+            .entries
+            .iter()
+            .cloned()
+            .chain(sources)
+            .filter_map(|entry| Some((DateKey::parse(&entry.date).ok()?, entry)))
+            .collect();
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct HolidayEntry {
+    /// Any `wallpapers.dates` key form.
+    pub date: String,
+    /// `holiday`, `workday` or a weekday name.
+    #[serde(rename = "as")]
+    pub behaves_as: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+}
+
+/// `(id, year, entries)`; a new year is a new file here.
+pub const BUNDLED_CALENDARS: &[(&str, i32, &str)] = &[
+    ("cn", 2025, include_str!("holidays/cn-2025.json")),
+    ("cn", 2026, include_str!("holidays/cn-2026.json")),
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DayKind {
+    Regular,
+    /// A make-up workday or other swapped day that follows another weekday's rules.
+    Remapped { weekday: Weekday, name: String },
+    Holiday { name: String },
+}
+
+impl fmt::Display for DayKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DayKind::Regular => write!(f, "regular day"),
+            DayKind::Remapped { weekday, name } => write!(f, "{}, follows {}", name, weekday_name(*weekday)),
+            DayKind::Holiday { name } => write!(f, "holiday: {}", name),
+        }
+    }
+}
+
+/// How `date` behaves: explicit entries win over `file`, which wins over the bundled calendar.
+pub fn day_kind(config: &Config, date: NaiveDate) -> DayKind {
+    let Some(calendar) = &config.holiday_calendar else {
+        return DayKind::Regular;
+    };
+    let Some((_, entry)) = calendar.resolved.iter().find(|(key, _)| key.contains(date)) else {
+        return DayKind::Regular;
+    };
+    let name = if entry.name.is_empty() { entry.date.clone() } else { entry.name.clone() };
+    match entry.behaves_as.to_lowercase().as_str() {
+        "holiday" => DayKind::Holiday { name },
+        "workday" => DayKind::Remapped {
+            weekday: calendar.workday_as.as_deref().and_then(weekday_from_str).unwrap_or(Weekday::Mon),
+            name,
+        },
+        other => match weekday_from_str(other) {
+            Some(weekday) => DayKind::Remapped { weekday, name },
+            None => DayKind::Regular,
+        },
+    }
+}
+
+/// The weekday whose `days` entry and specials apply on `date`.
+pub fn effective_weekday(config: &Config, date: NaiveDate) -> Weekday {
+    match day_kind(config, date) {
+        DayKind::Regular => date.weekday(),
+        DayKind::Remapped { weekday, .. } => weekday,
+        DayKind::Holiday { .. } => config
+            .holiday_calendar
+            .as_ref()
+            .and_then(|calendar| calendar.holiday_as.as_deref())
+            .and_then(weekday_from_str)
+            .unwrap_or(Weekday::Sun),
+    }
+}
+
+/// Every year bundled for `id`, oldest first.
+pub fn bundled_entries(id: &str) -> Vec<HolidayEntry> {
+    BUNDLED_CALENDARS
+        .iter()
+        .filter(|(name, _, _)| *name == id)
+        .flat_map(|(_, _, text)| serde_json::from_str::<Vec<HolidayEntry>>(text).unwrap_or_default())
+        .collect()
+}
+
+/// Whether the bundled calendar `id` has data for `year`.
+pub fn bundled_covers(id: &str, year: i32) -> bool {
+    BUNDLED_CALENDARS.iter().any(|(name, covered, _)| *name == id && *covered == year)
+}
+
+/// Reads `calendar.file` and the bundled calendar and fills in `calendar.resolved`.
+/// A file that cannot be read is reported, but the other entries still apply.
+pub fn load(config: &mut Config, base_dir: &Path) -> Result<(), String> {
+    let Some(calendar) = config.holiday_calendar.as_mut() else {
+        return Ok(());
+    };
+    let loaded = match &calendar.file {
+        Some(file) => read_file(&base_dir.join(file)),
+        None => Ok(Vec::new()),
+    };
+    let bundled = calendar.bundled.as_deref().map(bundled_entries).unwrap_or_default();
+    let sources = loaded.as_deref().unwrap_or_default().iter().cloned().chain(bundled).collect();
+    calendar.resolve(sources);
+    loaded.map(|_| ())
+}
+
+fn read_file(path: &Path) -> Result<Vec<HolidayEntry>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let entries = if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("ics")) {
+        parse_ics(&text)
+    } else {
+        serde_json::from_str(&text).map_err(|e| e.to_string())
+    };
+    entries.map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+/// Holiday feeds mark make-up workdays with 班 (or "workday") in the summary; every
+/// other all-day event is a holiday. `DTEND` is exclusive.
+fn parse_ics(text: &str) -> Result<Vec<HolidayEntry>, String> {
+    let mut entries = Vec::new();
+    for event in ics::parse_events(text)? {
+        let Some(start) = event.get("DTSTART").and_then(ics::parse_date) else {
+            continue;
+        };
+        let end = event
+            .get("DTEND")
+            .and_then(ics::parse_date)
+            .map(|end| end - Duration::days(1))
+            .filter(|end| *end >= start)
+            .unwrap_or(start);
+        let name = event.summary();
+        let behaves_as = if name.contains('班') || name.to_lowercase().contains("workday") {
+            "workday"
+        } else {
+            "holiday"
+        };
+        entries.push(HolidayEntry {
+            date: format!("{}..{}", start.format("%Y-%m-%d"), end.format("%Y-%m-%d")),
+            behaves_as: behaves_as.to_string(),
+            name,
+        });
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_config;
+
+    fn config(holiday_calendar: &str) -> Config {
+        let text = format!(r#"{{"version": 1, "wallpapers": {{}}, "holiday_calendar": {}}}"#, holiday_calendar);
+        let mut config = parse_config(&text).unwrap().0;
+        load(&mut config, Path::new("/nonexistent")).unwrap();
+        config
+    }
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn make_up_workdays_override_weekends() {
+        let config = config(r#"{"bundled": "cn"}"#);
+        // Sunday 2025-01-26 and Saturday 2025-02-08 are 春节 make-up workdays.
+        for day in ["2025-01-26", "2025-02-08"] {
+            assert_eq!(
+                day_kind(&config, date(day)),
+                DayKind::Remapped {
+                    weekday: Weekday::Mon,
+                    name: "春节调休".to_string()
+                }
+            );
+            assert_eq!(effective_weekday(&config, date(day)), Weekday::Mon);
+        }
+        let config = self::config(r#"{"bundled": "cn", "workday_as": "friday"}"#);
+        assert_eq!(effective_weekday(&config, date("2025-02-08")), Weekday::Fri);
+    }
+
+    #[test]
+    fn holidays_follow_holiday_as() {
+        let config = config(r#"{"bundled": "cn"}"#);
+        // Wednesday in the middle of 春节.
+        assert_eq!(
+            day_kind(&config, date("2025-01-29")),
+            DayKind::Holiday {
+                name: "春节".to_string()
+            }
+        );
+        assert_eq!(effective_weekday(&config, date("2025-01-29")), Weekday::Sun);
+        assert_eq!(effective_weekday(&config, date("2025-03-05")), Weekday::Wed);
+        assert_eq!(effective_weekday(&config, date("2026-10-02")), Weekday::Sun);
+        let config = self::config(r#"{"bundled": "cn", "holiday_as": "saturday"}"#);
+        assert_eq!(effective_weekday(&config, date("2025-01-29")), Weekday::Sat);
+    }
+
+    #[test]
+    fn own_entries_win_over_the_bundled_calendar() {
+        let config = config(
+            r#"{"bundled": "cn", "entries": [
+                {"date": "2025-10-08", "as": "workday", "name": "school"},
+                {"date": "2025-03-10", "as": "friday"},
+                {"date": "not a date", "as": "holiday"}
+            ]}"#,
+        );
+        assert_eq!(effective_weekday(&config, date("2025-10-08")), Weekday::Mon);
+        assert_eq!(effective_weekday(&config, date("2025-10-07")), Weekday::Sun);
+        assert_eq!(
+            day_kind(&config, date("2025-03-10")),
+            DayKind::Remapped {
+                weekday: Weekday::Fri,
+                name: "2025-03-10".to_string()
+            }
+        );
+        assert_eq!(config.holiday_calendar.unwrap().resolved.len(), 2 + bundled_entries("cn").len());
+    }
+
+    #[test]
+    fn a_missing_file_keeps_the_other_entries() {
+        let text = r#"{"version": 1, "wallpapers": {}, "holiday_calendar": {"bundled": "cn", "file": "missing.json"}}"#;
+        let mut config = parse_config(text).unwrap().0;
+        assert!(load(&mut config, Path::new("/nonexistent")).is_err());
+        assert_eq!(effective_weekday(&config, date("2025-01-26")), Weekday::Mon);
+    }
+
+    #[test]
+    fn reads_holiday_feeds() {
+        let text = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nDTSTART;VALUE=DATE:20250128\r\nDTEND;VALUE=DATE:20250205\r\nSUMMARY:春节\r\nEND:VEVENT\r\nBEGIN:VEVENT\r\nDTSTART;VALUE=DATE:20250208\r\nSUMMARY:春节补班\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let entries = parse_ics(text).unwrap();
+        let parsed: Vec<(&str, &str)> = entries.iter().map(|e| (e.date.as_str(), e.behaves_as.as_str())).collect();
+        assert_eq!(parsed, [("2025-01-28..2025-02-04", "holiday"), ("2025-02-08..2025-02-08", "workday")]);
+    }
+}
@@ -0,0 +1,260 @@
+use crate::timezone::{self, Zone};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use log::warn;
+
+/// One VEVENT as `(name, params, value)` properties.
+#[derive(Debug, Clone, Default)]
+pub struct IcsEvent {
+    pub properties: Vec<(String, String, String)>,
+}
+
+impl IcsEvent {
+    /// The value of the first property called `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|(key, _, _)| key == name)
+            .map(|(_, _, value)| value.as_str())
+    }
+
+    /// Every `(params, value)` of a property that may repeat, like `EXDATE`.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = (&'a str, &'a str)> + 'a {
+        self.properties
+            .iter()
+            .filter(move |(key, _, _)| key == name)
+            .map(|(_, params, value)| (params.as_str(), value.as_str()))
+    }
+
+    /// The parameters (`VALUE=DATE;TZID=...`) of the first property called `name`.
+    pub fn params(&self, name: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|(key, _, _)| key == name)
+            .map(|(_, params, _)| params.as_str())
+    }
+
+    pub fn summary(&self) -> String {
+        self.get("SUMMARY").map(unescape).unwrap_or_default()
+    }
+}
+
+/// Splits an iCalendar file into its VEVENTs. Only what the schedule needs is kept.
+pub fn parse_events(text: &str) -> Result<Vec<IcsEvent>, String> {
+    let mut events = Vec::new();
+    let mut current: Option<IcsEvent> = None;
+    let mut saw_calendar = false;
+    for line in unfold(text) {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let (name, params) = name.split_once(';').unwrap_or((name, ""));
+        let name = name.to_ascii_uppercase();
+        match (name.as_str(), value) {
+            ("BEGIN", "VCALENDAR") => saw_calendar = true,
+            ("BEGIN", "VEVENT") => current = Some(IcsEvent::default()),
+            ("END", "VEVENT") => events.extend(current.take()),
+            _ => {
+                if let Some(event) = current.as_mut() {
+                    event.properties.push((name, params.to_string(), value.to_string()));
+                }
+            }
+        }
+    }
+    if !saw_calendar {
+        return Err("not an iCalendar file (no BEGIN:VCALENDAR)".to_string());
+    }
+    Ok(events)
+}
+
+/// Lines starting with a space or tab continue the previous line (RFC 5545 3.1).
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.trim_end_matches('\r');
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// Decodes TEXT escapes in one pass, so `\\n` stays a backslash and an `n`.
+pub fn unescape(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => text.push('\n'),
+            Some(escaped @ ('\\' | ',' | ';')) => text.push(escaped),
+            // Not a valid escape: keep it as written.
+            Some(other) => {
+                text.push('\\');
+                text.push(other);
+            }
+            None => text.push('\\'),
+        }
+    }
+    text
+}
+
+/// The date part of a `DATE` or `DATE-TIME` value.
+pub fn parse_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value.get(..8)?, "%Y%m%d").ok()
+}
+
+/// A `DATE` or `DATE-TIME` as wall time in `zone`, and whether it was a bare date. UTC
+/// values (`...Z`) and values with an IANA `TZID` are converted; floating values, and
+/// those whose `TZID` is not an IANA name, are taken as wall time in `zone`.
+pub fn parse_date_time(value: &str, params: &str, zone: Zone) -> Option<(NaiveDateTime, bool)> {
+    if params.to_ascii_uppercase().contains("VALUE=DATE") && !params.to_ascii_uppercase().contains("VALUE=DATE-TIME")
+        || value.len() == 8
+    {
+        return Some((parse_date(value)?.and_time(NaiveTime::MIN), true));
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        let utc = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some((zone.wall_time(Utc.from_utc_datetime(&utc).with_timezone(&Local)), false));
+    }
+    let naive = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    let Some(tzid) = param(params, "TZID") else {
+        return Some((naive, false));
+    };
+    match tzid.parse::<Tz>() {
+        Ok(tz) => Some((zone.wall_time(timezone::resolve(&tz, naive)?.with_timezone(&Local)), false)),
+        Err(_) => {
+            warn!("Unknown TZID '{}', reading {} as wall time in the schedule's timezone", tzid, value);
+            Some((naive, false))
+        }
+    }
+}
+
+/// The value of parameter `name` in `VALUE=DATE;TZID="Europe/Berlin"`, unquoted.
+fn param<'a>(params: &'a str, name: &str) -> Option<&'a str> {
+    params
+        .split(';')
+        .filter_map(|param| param.split_once('='))
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim_matches('"'))
+}
+
+/// `DURATION` values like `PT1H30M` or `P1D`.
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let rest = value.strip_prefix('P')?;
+    let (days, time) = rest.split_once('T').unwrap_or((rest, ""));
+    let mut total = Duration::zero();
+    for (part, units) in [(days, &[('W', 7 * 24 * 60), ('D', 24 * 60)][..]), (time, &[('H', 60), ('M', 1), ('S', 0)][..])] {
+        let mut number = String::new();
+        for c in part.chars() {
+            if c.is_ascii_digit() {
+                number.push(c);
+                continue;
+            }
+            let &(_, minutes) = units.iter().find(|(unit, _)| *unit == c)?;
+            total += Duration::minutes(number.parse::<i64>().ok()? * minutes);
+            number.clear();
+        }
+    }
+    Some(total)
+}
+
+pub fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Folds a content line at 75 octets without splitting a UTF-8 character (RFC 5545 3.1).
+pub fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+pub fn format_utc(at: DateTime<Local>) -> String {
+    at.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shanghai() -> Zone {
+        Zone::Named(chrono_tz::Asia::Shanghai)
+    }
+
+    fn at(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn unescapes_text() {
+        assert_eq!(unescape("a\\, b\\; c\\nd\\\\e"), "a, b; c\nd\\e");
+        assert_eq!(unescape(&escape("x, y; z\\")), "x, y; z\\");
+        assert_eq!(escape("C:\\new"), "C:\\\\new");
+        assert_eq!(unescape(&escape("C:\\new")), "C:\\new");
+        assert_eq!(unescape(&escape("a\\,b\\;c\\")), "a\\,b\\;c\\");
+        assert_eq!(unescape("odd\\x"), "odd\\x");
+    }
+
+    #[test]
+    fn date_time_forms() {
+        let zone = shanghai();
+        assert_eq!(parse_date_time("20261020", "VALUE=DATE", zone), Some((at("2026-10-20 00:00"), true)));
+        assert_eq!(parse_date_time("20261020T120000", "", zone), Some((at("2026-10-20 12:00"), false)));
+        assert_eq!(parse_date_time("20261020T040000Z", "", zone), Some((at("2026-10-20 12:00"), false)));
+        assert_eq!(parse_date_time("2026-10-20", "", zone), None);
+    }
+
+    #[test]
+    fn tzid_is_converted() {
+        let zone = shanghai();
+        // 12:00 in Berlin is 18:00 in Shanghai in October and 19:00 in December.
+        assert_eq!(
+            parse_date_time("20261020T120000", "TZID=Europe/Berlin", zone),
+            Some((at("2026-10-20 18:00"), false))
+        );
+        assert_eq!(
+            parse_date_time("20261220T120000", "VALUE=DATE-TIME;TZID=\"Europe/Berlin\"", zone),
+            Some((at("2026-12-20 19:00"), false))
+        );
+        // Not an IANA name: read as floating time.
+        assert_eq!(
+            parse_date_time("20261020T120000", "TZID=W. Europe Standard Time", zone),
+            Some((at("2026-10-20 12:00"), false))
+        );
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("PT1H30M"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("P1W2D"), Some(Duration::days(9)));
+        assert_eq!(parse_duration("1H"), None);
+    }
+
+    #[test]
+    fn unfolds_and_parses_events() {
+        let text = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nSUMMARY:Long\r\n  title\r\nDTSTART;TZID=Asia/Shanghai:20261020T080000\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let events = parse_events(text).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].summary(), "Long title");
+        assert_eq!(events[0].params("DTSTART"), Some("TZID=Asia/Shanghai"));
+        assert!(parse_events("SUMMARY:x").is_err());
+    }
+}
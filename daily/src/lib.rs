@@ -0,0 +1,27 @@
+pub mod autostart;
+pub mod backend;
+pub mod calendars;
+pub mod clock;
+pub mod config;
+pub mod config_watcher;
+pub mod dates;
+pub mod file_manager;
+pub mod generate;
+pub mod holidays;
+pub mod ics;
+pub mod logger;
+pub mod overrides;
+pub mod playlist;
+pub mod recurrence;
+pub mod repeat;
+pub mod rules;
+pub mod schedule;
+pub mod simulate;
+pub mod solar;
+pub mod sources;
+pub mod state;
+pub mod terms;
+pub mod timezone;
+pub mod validate;
+pub mod web_server;
+pub mod wallpaper;
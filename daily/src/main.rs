@@ -1,20 +1,28 @@
 #![windows_subsystem = "windows"]
 use std::sync::Arc;
 use tokio::sync::{Mutex, Notify};
-use log::{error, info};
-mod autostart;
-mod config;
-mod file_manager;
-mod logger;
-mod state;
-mod web_server;
-mod wallpaper;
-use state::AppState;
+use log::{error, info, warn};
+use daily::backend::{self, HeadlessBackend, WallpaperBackend};
+use daily::clock::{Clock, SystemClock};
+use daily::playlist::Rotations;
+use daily::state::AppState;
+use daily::{autostart, config_watcher, file_manager, logger, simulate, wallpaper, web_server};
 
 const CONFIG_JSON_URL: &str = "https://gh-proxy.com/https://github.com/zxymiku/wallpaper/releases/download/config/config.json";
 
 #[tokio::main]
 async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("simulate") {
+        attach_console();
+        let result = simulate::parse_args(&args[1..], &SystemClock).and_then(|options| simulate::run(&options));
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let app_data_dir = match file_manager::init_app_data_dir() {
         Ok(dir) => dir,
         Err(e) => {
@@ -31,13 +39,44 @@ async fn main() {
     if let Err(e) = autostart::set_autostart() {
         error!("Failed to set autostart: {}", e);
     }
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+    let headless_dir = headless_dir_from_args(&args, &app_data_dir);
+    let backend: Box<dyn WallpaperBackend> = match headless_dir {
+        Some(dir) => match HeadlessBackend::new(dir, clock.clone()) {
+            Ok(backend) => Box::new(backend),
+            Err(e) => {
+                error!("Failed to set up headless backend: {}", e);
+                return;
+            }
+        },
+        None => match backend::detect_backend() {
+            Ok(backend) => backend,
+            Err(e) => {
+                warn!("No desktop wallpaper backend ({}), rendering to {} instead.", e, app_data_dir.display());
+                match HeadlessBackend::new(app_data_dir.clone(), clock.clone()) {
+                    Ok(backend) => Box::new(backend),
+                    Err(e) => {
+                        error!("Failed to set up headless backend: {}", e);
+                        return;
+                    }
+                }
+            }
+        },
+    };
+    info!("Using {} wallpaper backend", backend.name());
 let app_state = Arc::new(AppState {
         config: Mutex::new(None),
+        remote_config: Mutex::new(None),
+        provenance: Mutex::new(Default::default()),
         temp_wallpaper: Mutex::new(None),
+        rotations: Mutex::new(Rotations::load(&app_data_dir)),
         current_wallpaper_url: Mutex::new(String::new()),
+        written_config: Mutex::new(None),
         app_data_dir: app_data_dir.clone(),
         wallpaper_notify: Notify::new(),
         web_wallpaper_pid: Mutex::new(None),
+        backend,
+        clock,
     });
     let autostart_handle = tokio::spawn(async {
         autostart::check_loop().await;
@@ -46,6 +85,10 @@ let app_state = Arc::new(AppState {
     let cleanup_handle = tokio::spawn(async move {
         file_manager::cleanup_loop(cleanup_state, CONFIG_JSON_URL).await;
     });
+    let watcher_state = app_state.clone();
+    let watcher_handle = tokio::spawn(async move {
+        config_watcher::watch_loop(watcher_state).await;
+    });
     let wallpaper_state = app_state.clone();
     let wallpaper_handle = tokio::spawn(async move {
         wallpaper::wallpaper_loop(wallpaper_state).await;
@@ -54,6 +97,29 @@ let app_state = Arc::new(AppState {
     if let Err(e) = web_server::start_server(app_state).await {
         error!("Web server failed: {}", e);
     }
-    _ = tokio::join!(autostart_handle, cleanup_handle, wallpaper_handle);
+    _ = tokio::join!(autostart_handle, cleanup_handle, watcher_handle, wallpaper_handle);
     info!("--- Daily Wallpaper application shutting down ---");
-}
\ No newline at end of file
+}
+
+/// `--headless [dir]` renders wallpapers to files instead of the desktop.
+fn headless_dir_from_args(args: &[String], app_data_dir: &std::path::Path) -> Option<std::path::PathBuf> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--headless" {
+            return Some(args.next().map(Into::into).unwrap_or_else(|| app_data_dir.to_path_buf()));
+        }
+    }
+    None
+}
+
+/// Release builds use the windows subsystem, so borrow the parent console for CLI output.
+#[cfg(windows)]
+fn attach_console() {
+    use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS).ok();
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
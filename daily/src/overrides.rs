@@ -0,0 +1,477 @@
+use crate::calendars::CalendarSource;
+use crate::config::{Config, Days, SpecialPeriod};
+use crate::holidays::HolidayCalendar;
+use crate::playlist::Playlist;
+use crate::rules::Rule;
+use crate::solar::Location;
+use crate::terms::Term;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Machine-local `override.json`, layered on top of the remote config. Every field is
+/// optional; anything left out keeps the remote value.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ConfigOverride {
+    #[serde(default)]
+    pub wallpapers: WallpapersOverride,
+    #[serde(default)]
+    pub special_urls: HashMap<String, String>,
+    /// Replaces remote playlists of the same name.
+    #[serde(default)]
+    pub playlists: HashMap<String, Playlist>,
+    /// Replaces the remote `terms` as a whole when present.
+    #[serde(default)]
+    pub terms: Option<Vec<Term>>,
+    /// Replaces the remote `holiday_calendar` as a whole when present.
+    #[serde(default)]
+    pub holiday_calendar: Option<HolidayCalendar>,
+    /// Replaces the remote `calendars` as a whole when present.
+    #[serde(default)]
+    pub calendars: Option<Vec<CalendarSource>>,
+    /// Replaces the remote `timezone`, e.g. for a machine whose own clock zone is wrong.
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// Replaces the remote `location`, for a machine somewhere else.
+    #[serde(default)]
+    pub location: Option<Location>,
+    /// Added in front of the remote `rules`, so they win ties.
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    /// URLs (or special_urls ids) that must never be shown on this machine.
+    #[serde(default)]
+    pub blocked_urls: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct WallpapersOverride {
+    /// Replaces the remote `days` key by key, groups included.
+    #[serde(default)]
+    pub days: Days,
+    #[serde(default)]
+    pub dates: HashMap<String, String>,
+    #[serde(default)]
+    pub specials: Vec<SpecialPeriod>,
+    pub term_start: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Origin {
+    Remote,
+    Override,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LayerEntry {
+    pub path: String,
+    pub value: String,
+    pub origin: Origin,
+}
+
+/// Where each value of a merged config came from, and what `blocked_urls` removed.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Provenance {
+    pub entries: Vec<LayerEntry>,
+    pub blocked: Vec<LayerEntry>,
+}
+
+pub fn parse_override(text: &str) -> Result<ConfigOverride, String> {
+    serde_json::from_str(text).map_err(|e| e.to_string())
+}
+
+/// Merges `layer` over `base`:
+/// - days (groups included), dates, special_urls and playlists are replaced key by key;
+/// - override specials and rules come first, so they win over remote ones for the same time;
+/// - entries and playlist items whose target is blocked are dropped, blocked days become empty;
+///   calendar events are dropped by `block_events` once loaded.
+pub fn merge(base: &Config, layer: Option<&ConfigOverride>) -> (Config, Provenance) {
+    let empty = ConfigOverride::default();
+    let layer = layer.unwrap_or(&empty);
+    let mut merged = base.clone();
+    let mut provenance = Provenance::default();
+    let is_blocked = |target: &str| blocks(base, layer, target);
+
+    if let Some(timezone) = &layer.timezone {
+        merged.timezone = Some(timezone.clone());
+    }
+    if let Some(timezone) = &merged.timezone {
+        provenance.entries.push(LayerEntry {
+            path: "$.timezone".to_string(),
+            value: timezone.clone(),
+            origin: if layer.timezone.is_some() { Origin::Override } else { Origin::Remote },
+        });
+    }
+    if let Some(location) = layer.location {
+        merged.location = Some(location);
+    }
+    if let Some(location) = merged.location {
+        provenance.entries.push(LayerEntry {
+            path: "$.location".to_string(),
+            value: format!("{}, {}", location.latitude, location.longitude),
+            origin: if layer.location.is_some() { Origin::Override } else { Origin::Remote },
+        });
+    }
+    if let Some(term_start) = &layer.wallpapers.term_start {
+        merged.wallpapers.term_start = Some(term_start.clone());
+    }
+    if let Some(terms) = &layer.terms {
+        merged.terms = terms.clone();
+    }
+    for (i, term) in merged.terms.iter().enumerate() {
+        provenance.entries.push(LayerEntry {
+            path: format!("$.terms[{}]", i),
+            value: format!("{} {}..{}", term.name, term.start, term.end),
+            origin: if layer.terms.is_some() { Origin::Override } else { Origin::Remote },
+        });
+    }
+    if let Some(calendar) = &layer.holiday_calendar {
+        merged.holiday_calendar = Some(calendar.clone());
+    }
+    if let Some(calendar) = &merged.holiday_calendar {
+        let sources: Vec<&str> = calendar.bundled.iter().chain(&calendar.file).map(String::as_str).collect();
+        provenance.entries.push(LayerEntry {
+            path: "$.holiday_calendar".to_string(),
+            value: format!("{} ({} entries)", sources.join(", "), calendar.entries.len()),
+            origin: if layer.holiday_calendar.is_some() { Origin::Override } else { Origin::Remote },
+        });
+    }
+    if let Some(calendars) = &layer.calendars {
+        merged.calendars = calendars.clone();
+    }
+    for (i, calendar) in merged.calendars.iter().enumerate() {
+        provenance.entries.push(LayerEntry {
+            path: format!("$.calendars[{}]", i),
+            value: calendar.source.clone(),
+            origin: if layer.calendars.is_some() { Origin::Override } else { Origin::Remote },
+        });
+    }
+    if let Some(term_start) = &merged.wallpapers.term_start {
+        provenance.entries.push(LayerEntry {
+            path: "$.wallpapers.term_start".to_string(),
+            value: term_start.clone(),
+            origin: if layer.wallpapers.term_start.is_some() { Origin::Override } else { Origin::Remote },
+        });
+    }
+
+    let over = layer.wallpapers.days.entries();
+    for ((name, slot), (_, replacement)) in merged.wallpapers.days.entries_mut().into_iter().zip(over) {
+        let origin = match replacement {
+            Some(url) => {
+                *slot = Some(url.clone());
+                Origin::Override
+            }
+            None => Origin::Remote,
+        };
+        let Some(slot) = slot else {
+            continue;
+        };
+        let entry = LayerEntry {
+            path: format!("$.wallpapers.days.{}", name),
+            value: slot.clone(),
+            origin,
+        };
+        if is_blocked(slot) {
+            // Cleared rather than removed, so a blocked day does not fall back to its group.
+            slot.clear();
+            provenance.blocked.push(entry);
+        } else {
+            provenance.entries.push(entry);
+        }
+    }
+
+    let mut dates: Vec<(String, String, Origin)> = base
+        .wallpapers
+        .dates
+        .iter()
+        .filter(|(key, _)| !layer.wallpapers.dates.contains_key(*key))
+        .map(|(key, url)| (key.clone(), url.clone(), Origin::Remote))
+        .chain(
+            layer
+                .wallpapers
+                .dates
+                .iter()
+                .map(|(key, url)| (key.clone(), url.clone(), Origin::Override)),
+        )
+        .collect();
+    dates.sort_by(|a, b| a.0.cmp(&b.0));
+    merged.wallpapers.dates.clear();
+    for (key, url, origin) in dates {
+        let entry = LayerEntry {
+            path: format!("$.wallpapers.dates[\"{}\"]", key),
+            value: url.clone(),
+            origin,
+        };
+        if is_blocked(&url) {
+            provenance.blocked.push(entry);
+        } else {
+            merged.wallpapers.dates.insert(key, url);
+            provenance.entries.push(entry);
+        }
+    }
+
+    let specials = layer
+        .wallpapers
+        .specials
+        .iter()
+        .enumerate()
+        .map(|(i, period)| (i, period, Origin::Override))
+        .chain(base.wallpapers.specials.iter().enumerate().map(|(i, period)| (i, period, Origin::Remote)));
+    merged.wallpapers.specials.clear();
+    for (i, period, origin) in specials {
+        let end = match &period.end_day {
+            Some(end_day) => format!("{} {}", end_day, period.end),
+            None => period.end.clone(),
+        };
+        let day = period.repeat.as_ref().unwrap_or(&period.day);
+        let value = format!("{} {}-{} {}", day, period.start, end, period.url);
+        if is_blocked(&period.url) {
+            // Blocked specials are not in the merged list, so point at their own layer.
+            provenance.blocked.push(LayerEntry {
+                path: format!("$.wallpapers.specials[{}]", i),
+                value,
+                origin,
+            });
+        } else {
+            provenance.entries.push(LayerEntry {
+                path: format!("$.wallpapers.specials[{}]", merged.wallpapers.specials.len()),
+                value,
+                origin,
+            });
+            merged.wallpapers.specials.push(period.clone());
+        }
+    }
+
+    let rules = layer
+        .rules
+        .iter()
+        .enumerate()
+        .map(|(i, rule)| (i, rule, Origin::Override))
+        .chain(base.rules.iter().enumerate().map(|(i, rule)| (i, rule, Origin::Remote)));
+    merged.rules.clear();
+    for (i, rule, origin) in rules {
+        let value = format!("priority {} {}", rule.priority, rule.url);
+        if is_blocked(&rule.url) {
+            provenance.blocked.push(LayerEntry {
+                path: format!("$.rules[{}]", i),
+                value,
+                origin,
+            });
+        } else {
+            provenance.entries.push(LayerEntry {
+                path: format!("$.rules[{}]", merged.rules.len()),
+                value,
+                origin,
+            });
+            merged.rules.push(rule.clone());
+        }
+    }
+
+    let mut ids: Vec<&String> = base.special_urls.keys().chain(layer.special_urls.keys()).collect();
+    ids.sort();
+    ids.dedup();
+    merged.special_urls.clear();
+    for id in ids {
+        let (url, origin) = match layer.special_urls.get(id) {
+            Some(url) => (url, Origin::Override),
+            None => (&base.special_urls[id], Origin::Remote),
+        };
+        let entry = LayerEntry {
+            path: format!("$.special_urls.{}", id),
+            value: url.clone(),
+            origin,
+        };
+        if is_blocked(id) {
+            provenance.blocked.push(entry);
+        } else {
+            merged.special_urls.insert(id.clone(), url.clone());
+            provenance.entries.push(entry);
+        }
+    }
+
+    let mut names: Vec<&String> = base.playlists.keys().chain(layer.playlists.keys()).collect();
+    names.sort();
+    names.dedup();
+    merged.playlists.clear();
+    for name in names {
+        let (playlist, origin) = match layer.playlists.get(name) {
+            Some(playlist) => (playlist, Origin::Override),
+            None => (&base.playlists[name], Origin::Remote),
+        };
+        let mut playlist = playlist.clone();
+        playlist.items.retain(|item| {
+            let blocked = is_blocked(item.url());
+            if blocked {
+                provenance.blocked.push(LayerEntry {
+                    path: format!("$.playlists.{}.items", name),
+                    value: item.url().to_string(),
+                    origin,
+                });
+            }
+            !blocked
+        });
+        provenance.entries.push(LayerEntry {
+            path: format!("$.playlists.{}", name),
+            value: format!(
+                "{} items, {:?} every {} min",
+                playlist.items.len(),
+                playlist.order,
+                playlist.interval_minutes
+            )
+            .to_lowercase(),
+            origin,
+        });
+        merged.playlists.insert(name.clone(), playlist);
+    }
+
+    (merged, provenance)
+}
+
+/// Drops calendar events whose target is blocked. Events are only known once the
+/// calendars are loaded, which happens after `merge`.
+pub fn block_events(merged: &mut Config, base: &Config, layer: Option<&ConfigOverride>, provenance: &mut Provenance) {
+    let Some(layer) = layer else {
+        return;
+    };
+    let origin = if layer.calendars.is_some() { Origin::Override } else { Origin::Remote };
+    for (i, calendar) in merged.calendars.iter_mut().enumerate() {
+        calendar.events.retain(|event| {
+            let blocked = blocks(base, layer, &event.target);
+            if blocked {
+                provenance.blocked.push(LayerEntry {
+                    path: format!("$.calendars[{}]", i),
+                    value: format!("\"{}\" {}", event.summary, event.target),
+                    origin,
+                });
+            }
+            !blocked
+        });
+    }
+}
+
+fn blocks(base: &Config, layer: &ConfigOverride, target: &str) -> bool {
+    !target.is_empty()
+        && layer.blocked_urls.iter().any(|blocked| {
+            blocked == target || merged_special_url(base, layer, target).is_some_and(|url| url == blocked)
+        })
+}
+
+fn merged_special_url<'a>(base: &'a Config, layer: &'a ConfigOverride, id: &str) -> Option<&'a String> {
+    layer.special_urls.get(id).or_else(|| base.special_urls.get(id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendars::CalendarEvent;
+    use crate::config::parse_config;
+    use chrono::{Duration, NaiveDate};
+
+    fn remote() -> Config {
+        parse_config(
+            r#"{"version": 1, "wallpapers": {
+                "days": {"monday": "https://example.com/monday.jpg", "weekdays": "https://example.com/weekdays.jpg",
+                         "default": "https://example.com/default.jpg"},
+                "dates": {"10-01": "lobby", "12-25": "https://example.com/xmas.jpg"},
+                "specials": [{"day": "friday", "start": "18:00", "end": "20:00", "url": "https://example.com/friday.jpg"}]
+            }, "special_urls": {"lobby": "https://example.com/lobby.html"},
+               "calendars": [{"source": "school.ics"}]}"#,
+        )
+        .unwrap()
+        .0
+    }
+
+    fn layer(text: &str) -> ConfigOverride {
+        parse_override(text).unwrap()
+    }
+
+    fn entry<'a>(entries: &'a [LayerEntry], path: &str) -> Option<&'a LayerEntry> {
+        entries.iter().find(|entry| entry.path == path)
+    }
+
+    #[test]
+    fn override_beats_remote() {
+        let layer = layer(
+            r#"{"wallpapers": {"days": {"monday": "https://example.com/mine.jpg"}, "dates": {"12-25": ""},
+                "specials": [{"day": "friday", "start": "19:00", "end": "21:00", "url": "https://example.com/late.jpg"}]},
+                "special_urls": {"lobby": "https://example.com/other.html"}, "timezone": "Europe/Berlin"}"#,
+        );
+        let (merged, provenance) = merge(&remote(), Some(&layer));
+        assert_eq!(merged.wallpapers.days.monday.as_deref(), Some("https://example.com/mine.jpg"));
+        assert_eq!(merged.wallpapers.days.weekdays.as_deref(), Some("https://example.com/weekdays.jpg"));
+        assert_eq!(merged.wallpapers.dates["12-25"], "");
+        assert_eq!(merged.wallpapers.dates["10-01"], "lobby");
+        assert_eq!(merged.special_urls["lobby"], "https://example.com/other.html");
+        assert_eq!(merged.timezone.as_deref(), Some("Europe/Berlin"));
+        // Override specials come first, so they win the same time.
+        let urls: Vec<&str> = merged.wallpapers.specials.iter().map(|p| p.url.as_str()).collect();
+        assert_eq!(urls, ["https://example.com/late.jpg", "https://example.com/friday.jpg"]);
+
+        let monday = entry(&provenance.entries, "$.wallpapers.days.monday").unwrap();
+        assert_eq!(monday.origin, Origin::Override);
+        assert_eq!(entry(&provenance.entries, "$.wallpapers.days.weekdays").unwrap().origin, Origin::Remote);
+        assert_eq!(entry(&provenance.entries, "$.wallpapers.dates[\"12-25\"]").unwrap().origin, Origin::Override);
+        assert_eq!(entry(&provenance.entries, "$.wallpapers.specials[0]").unwrap().origin, Origin::Override);
+        assert_eq!(entry(&provenance.entries, "$.wallpapers.specials[1]").unwrap().origin, Origin::Remote);
+        assert!(provenance.blocked.is_empty());
+    }
+
+    #[test]
+    fn no_override_keeps_the_remote_config() {
+        let (merged, provenance) = merge(&remote(), None);
+        assert_eq!(merged.wallpapers.days.monday.as_deref(), Some("https://example.com/monday.jpg"));
+        assert!(provenance.entries.iter().all(|entry| entry.origin == Origin::Remote));
+    }
+
+    #[test]
+    fn blocked_urls_clear_days_and_drop_entries() {
+        let layer = layer(
+            r#"{"blocked_urls": ["https://example.com/monday.jpg", "https://example.com/lobby.html",
+                                  "https://example.com/friday.jpg"]}"#,
+        );
+        let (merged, provenance) = merge(&remote(), Some(&layer));
+        // Cleared, not removed, so Monday does not fall back to weekdays.
+        assert_eq!(merged.wallpapers.days.monday.as_deref(), Some(""));
+        assert_eq!(merged.wallpapers.days.lookup(chrono::Weekday::Mon), Some(("monday", "")));
+        // The date points at a special_urls id whose URL is blocked.
+        assert!(!merged.wallpapers.dates.contains_key("10-01"));
+        assert!(!merged.special_urls.contains_key("lobby"));
+        assert!(merged.wallpapers.specials.is_empty());
+
+        let blocked: Vec<&str> = provenance.blocked.iter().map(|entry| entry.path.as_str()).collect();
+        assert_eq!(
+            blocked,
+            [
+                "$.wallpapers.days.monday",
+                "$.wallpapers.dates[\"10-01\"]",
+                "$.wallpapers.specials[0]",
+                "$.special_urls.lobby"
+            ]
+        );
+        assert_eq!(provenance.blocked[0].value, "https://example.com/monday.jpg");
+        assert_eq!(provenance.blocked[0].origin, Origin::Remote);
+    }
+
+    #[test]
+    fn block_events_drops_blocked_calendar_events() {
+        let base = remote();
+        let layer = layer(r#"{"blocked_urls": ["https://example.com/lobby.html"]}"#);
+        let (mut merged, mut provenance) = merge(&base, Some(&layer));
+        let event = |summary: &str, target: &str| CalendarEvent {
+            summary: summary.to_string(),
+            target: target.to_string(),
+            start: NaiveDate::from_ymd_opt(2026, 10, 20).unwrap().and_hms_opt(9, 0, 0).unwrap(),
+            duration: Duration::hours(1),
+            recurrence: None,
+            exdates: Vec::new(),
+        };
+        merged.calendars[0].events = vec![event("Open day", "lobby"), event("Exam", "https://example.com/exam.jpg")];
+
+        block_events(&mut merged, &base, Some(&layer), &mut provenance);
+        let kept: Vec<&str> = merged.calendars[0].events.iter().map(|e| e.summary.as_str()).collect();
+        assert_eq!(kept, ["Exam"]);
+        let blocked = provenance.blocked.last().unwrap();
+        assert_eq!(blocked.path, "$.calendars[0]");
+        assert_eq!(blocked.value, "\"Open day\" lobby");
+        assert_eq!(blocked.origin, Origin::Remote);
+    }
+}
@@ -0,0 +1,322 @@
+use crate::backend::write_atomically;
+use chrono::{DateTime, Duration, Local};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+pub const ROTATIONS_FILE_NAME: &str = "playlists.json";
+
+/// Wallpapers a slot rotates through. Slots refer to a playlist by name, like a
+/// `special_urls` id.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Playlist {
+    /// URLs or special_urls ids.
+    #[serde(default)]
+    pub items: Vec<PlaylistItem>,
+    /// A `file://` directory whose images join `items`, picked up as they are added.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub directory: Option<String>,
+    /// How long each item stays up.
+    pub interval_minutes: u32,
+    #[serde(default)]
+    pub order: Order,
+}
+
+/// A plain URL, or `{"url": ..., "weight": 3}` for `weighted` playlists.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum PlaylistItem {
+    Url(String),
+    Weighted { url: String, weight: u32 },
+}
+
+impl PlaylistItem {
+    pub fn url(&self) -> &str {
+        match self {
+            PlaylistItem::Url(url) | PlaylistItem::Weighted { url, .. } => url,
+        }
+    }
+
+    pub fn weight(&self) -> u32 {
+        match self {
+            PlaylistItem::Url(_) => 1,
+            PlaylistItem::Weighted { weight, .. } => *weight,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Order {
+    #[default]
+    Sequential,
+    /// Every item once in random order, then a new round.
+    Shuffle,
+    /// Independent draws, each item as likely as its weight.
+    Weighted,
+}
+
+/// Where a playlist is: the item up now and the one after it, which is drawn in
+/// advance so it can be downloaded before it is due.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Rotation {
+    pub current: String,
+    pub next: String,
+    pub changed_at: DateTime<Local>,
+    /// Shuffle items left in this round, `next` not included.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remaining: Vec<String>,
+}
+
+/// What a playlist shows now, what comes next and when.
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub item: String,
+    pub next: String,
+    pub due: DateTime<Local>,
+}
+
+/// Every playlist's rotation, kept in `playlists.json` so a restart carries on where
+/// it left off.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Rotations(HashMap<String, Rotation>);
+
+impl Rotations {
+    pub fn load(app_data_dir: &Path) -> Rotations {
+        let path = app_data_dir.join(ROTATIONS_FILE_NAME);
+        match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+                warn!("Failed to parse {}, starting playlists over: {}", path.display(), e);
+                Rotations::default()
+            }),
+            Err(_) => Rotations::default(),
+        }
+    }
+
+    pub fn save(&self, app_data_dir: &Path) -> Result<(), String> {
+        let path = app_data_dir.join(ROTATIONS_FILE_NAME);
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        write_atomically(&path, text.as_bytes())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Rotation> {
+        self.0.get(name)
+    }
+
+    /// The step `name` is on at `now`, moving on one item once its interval is up.
+    /// Returns whether the rotation changed and should be saved.
+    pub fn step(&mut self, name: &str, playlist: &Playlist, now: DateTime<Local>) -> Option<(Step, bool)> {
+        if playlist.items.is_empty() {
+            return None;
+        }
+        let interval = Duration::minutes(playlist.interval_minutes.max(1) as i64);
+        let contains = |url: &str| playlist.items.iter().any(|item| item.url() == url);
+        let mut changed = false;
+        // An item dropped from the config restarts the rotation.
+        if !self.0.get(name).is_some_and(|rotation| contains(&rotation.current)) {
+            let mut remaining = Vec::new();
+            let current = draw(playlist, None, &mut remaining);
+            let next = draw(playlist, Some(&current), &mut remaining);
+            self.0.insert(
+                name.to_string(),
+                Rotation {
+                    current,
+                    next,
+                    changed_at: now,
+                    remaining,
+                },
+            );
+            changed = true;
+        }
+        let rotation = self.0.get_mut(name)?;
+        if !contains(&rotation.next) {
+            rotation.remaining.retain(|url| contains(url));
+            rotation.next = draw(playlist, Some(&rotation.current), &mut rotation.remaining);
+            changed = true;
+        }
+        let elapsed = now - rotation.changed_at;
+        if elapsed >= interval {
+            // After a long sleep only move on one item, but keep the cadence.
+            let periods = (elapsed.num_seconds() / interval.num_seconds()) as i32;
+            rotation.current = std::mem::take(&mut rotation.next);
+            rotation.remaining.retain(|url| contains(url));
+            rotation.next = draw(playlist, Some(&rotation.current), &mut rotation.remaining);
+            rotation.changed_at += interval * periods;
+            changed = true;
+        } else if elapsed < Duration::zero() {
+            // The clock went back.
+            rotation.changed_at = now;
+            changed = true;
+        }
+        let step = Step {
+            item: rotation.current.clone(),
+            next: rotation.next.clone(),
+            due: rotation.changed_at + interval,
+        };
+        Some((step, changed))
+    }
+}
+
+/// The item to show after `after`.
+fn draw(playlist: &Playlist, after: Option<&str>, remaining: &mut Vec<String>) -> String {
+    let urls: Vec<&str> = playlist.items.iter().map(PlaylistItem::url).collect();
+    match playlist.order {
+        Order::Sequential => {
+            let position = after.and_then(|after| urls.iter().position(|url| *url == after));
+            urls[position.map_or(0, |i| (i + 1) % urls.len())].to_string()
+        }
+        Order::Shuffle => {
+            if remaining.is_empty() {
+                remaining.extend(urls.iter().map(|url| url.to_string()));
+                fastrand::shuffle(remaining);
+                // A new round should not start with what was just shown.
+                if remaining.len() > 1 && remaining.last().map(String::as_str) == after {
+                    let last = remaining.len() - 1;
+                    remaining.swap(0, last);
+                }
+            }
+            remaining.pop().unwrap_or_default()
+        }
+        Order::Weighted => {
+            let total: u32 = playlist.items.iter().map(PlaylistItem::weight).sum();
+            if total == 0 {
+                return urls[0].to_string();
+            }
+            let mut pick = fastrand::u32(0..total);
+            for item in &playlist.items {
+                if pick < item.weight() {
+                    return item.url().to_string();
+                }
+                pick -= item.weight();
+            }
+            urls[0].to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn start() -> DateTime<Local> {
+        Utc.with_ymd_and_hms(2026, 10, 18, 8, 0, 0).unwrap().with_timezone(&Local)
+    }
+
+    fn playlist(urls: &[&str], order: Order) -> Playlist {
+        Playlist {
+            items: urls.iter().map(|url| PlaylistItem::Url(url.to_string())).collect(),
+            directory: None,
+            interval_minutes: 10,
+            order,
+        }
+    }
+
+    fn step(rotations: &mut Rotations, playlist: &Playlist, now: DateTime<Local>) -> (String, String, DateTime<Local>, bool) {
+        let (step, changed) = rotations.step("p", playlist, now).unwrap();
+        (step.item, step.next, step.due, changed)
+    }
+
+    #[test]
+    fn sequential_moves_on_each_interval() {
+        let playlist = playlist(&["a", "b", "c"], Order::Sequential);
+        let mut rotations = Rotations::default();
+        let minutes = |n| start() + Duration::minutes(n);
+        assert_eq!(step(&mut rotations, &playlist, start()), ("a".into(), "b".into(), minutes(10), true));
+        assert_eq!(step(&mut rotations, &playlist, minutes(9)), ("a".into(), "b".into(), minutes(10), false));
+        assert_eq!(step(&mut rotations, &playlist, minutes(10)), ("b".into(), "c".into(), minutes(20), true));
+        assert_eq!(step(&mut rotations, &playlist, minutes(20)), ("c".into(), "a".into(), minutes(30), true));
+        assert!(rotations.step("p", &Playlist { items: Vec::new(), ..playlist }, start()).is_none());
+    }
+
+    #[test]
+    fn restarts_when_the_current_item_is_dropped() {
+        let mut rotations = Rotations::default();
+        step(&mut rotations, &playlist(&["a", "b", "c"], Order::Sequential), start());
+        step(&mut rotations, &playlist(&["a", "b", "c"], Order::Sequential), start() + Duration::minutes(10));
+        let now = start() + Duration::minutes(12);
+        let (item, next, due, changed) = step(&mut rotations, &playlist(&["a", "c"], Order::Sequential), now);
+        assert_eq!((item.as_str(), next.as_str(), changed), ("a", "c", true));
+        assert_eq!(due, now + Duration::minutes(10));
+
+        // Dropping only the next item redraws it but keeps the current one's time.
+        let (item, next, due, _) = step(&mut rotations, &playlist(&["a", "b"], Order::Sequential), now);
+        assert_eq!((item.as_str(), next.as_str()), ("a", "b"));
+        assert_eq!(due, now + Duration::minutes(10));
+    }
+
+    #[test]
+    fn keeps_the_cadence_after_a_long_sleep() {
+        let playlist = playlist(&["a", "b", "c"], Order::Sequential);
+        let mut rotations = Rotations::default();
+        step(&mut rotations, &playlist, start());
+        // 35 minutes later: one item on, not three, and still due on the 10-minute grid.
+        let (item, _, due, changed) = step(&mut rotations, &playlist, start() + Duration::minutes(35));
+        assert_eq!((item.as_str(), changed), ("b", true));
+        assert_eq!(due, start() + Duration::minutes(40));
+    }
+
+    #[test]
+    fn restarts_the_interval_when_the_clock_goes_back() {
+        let playlist = playlist(&["a", "b"], Order::Sequential);
+        let mut rotations = Rotations::default();
+        step(&mut rotations, &playlist, start());
+        let earlier = start() - Duration::hours(1);
+        let (item, _, due, changed) = step(&mut rotations, &playlist, earlier);
+        assert_eq!((item.as_str(), changed), ("a", true));
+        assert_eq!(due, earlier + Duration::minutes(10));
+    }
+
+    #[test]
+    fn shuffle_shows_each_item_once_per_round() {
+        let playlist = playlist(&["a", "b", "c", "d"], Order::Shuffle);
+        let mut rotations = Rotations::default();
+        let shown: Vec<String> = (0..40)
+            .map(|n| step(&mut rotations, &playlist, start() + Duration::minutes(10 * n)).0)
+            .collect();
+        for round in shown.chunks(4) {
+            let mut sorted = round.to_vec();
+            sorted.sort();
+            assert_eq!(sorted, ["a", "b", "c", "d"]);
+        }
+        // Not even across a round boundary.
+        assert!(shown.windows(2).all(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn weighted_skips_zero_weights_and_falls_back_to_the_first_item() {
+        let weighted = |weights: &[u32]| Playlist {
+            items: weights
+                .iter()
+                .enumerate()
+                .map(|(i, weight)| PlaylistItem::Weighted {
+                    url: format!("item{}", i),
+                    weight: *weight,
+                })
+                .collect(),
+            directory: None,
+            interval_minutes: 10,
+            order: Order::Weighted,
+        };
+        let playlist = weighted(&[0, 3, 0]);
+        for _ in 0..50 {
+            assert_eq!(draw(&playlist, None, &mut Vec::new()), "item1");
+        }
+        let playlist = weighted(&[0, 0]);
+        assert_eq!(draw(&playlist, Some("item0"), &mut Vec::new()), "item0");
+    }
+
+    #[test]
+    fn saves_and_loads_rotations() {
+        let dir = std::env::temp_dir().join(format!("daily-test-{}-rotations", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut rotations = Rotations::default();
+        step(&mut rotations, &playlist(&["a", "b"], Order::Sequential), start());
+        rotations.save(&dir).unwrap();
+        assert!(!dir.join("playlists.tmp").exists());
+        let loaded = Rotations::load(&dir);
+        assert_eq!(loaded.get("p").map(|rotation| rotation.next.as_str()), Some("b"));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,385 @@
+use crate::schedule::weekday_from_str;
+use crate::timezone::Zone;
+use chrono::{Datelike, Days, Local, Months, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+
+// A runaway rule (e.g. FREQ=DAILY from 1970) must not stall the scheduler.
+const MAX_CANDIDATES: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// The subset of RFC 5545 RRULE that timetables use: FREQ, INTERVAL, COUNT, UNTIL,
+/// BYDAY (with ordinals like `1MO` or `-1FR` for monthly and yearly rules), BYMONTHDAY
+/// and BYMONTH.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recurrence {
+    pub frequency: Frequency,
+    pub interval: u32,
+    pub count: Option<u32>,
+    pub until: Option<NaiveDateTime>,
+    pub by_day: Vec<(Option<i32>, Weekday)>,
+    pub by_month_day: Vec<i32>,
+    pub by_month: Vec<u32>,
+}
+
+impl Recurrence {
+    /// Parses `FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE;UNTIL=20270122T000000`. A UTC `UNTIL`
+    /// is converted to wall time in `zone`, the timezone the occurrences are in.
+    pub fn parse_rrule(value: &str, zone: Zone) -> Result<Recurrence, String> {
+        let value = value.strip_prefix("RRULE:").unwrap_or(value);
+        let mut frequency = None;
+        let mut rule = Recurrence {
+            frequency: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            by_day: Vec::new(),
+            by_month_day: Vec::new(),
+            by_month: Vec::new(),
+        };
+        for part in value.split(';').filter(|part| !part.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("'{}' is not KEY=VALUE", part))?;
+            match key.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return Err(format!("unsupported FREQ '{}'", value)),
+                    })
+                }
+                "INTERVAL" => {
+                    rule.interval = value
+                        .parse()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| format!("invalid INTERVAL '{}'", value))?
+                }
+                "COUNT" => rule.count = Some(value.parse().map_err(|_| format!("invalid COUNT '{}'", value))?),
+                "UNTIL" => {
+                    rule.until = Some(parse_until(value, zone).ok_or_else(|| format!("invalid UNTIL '{}'", value))?)
+                }
+                "BYDAY" => {
+                    for day in value.split(',') {
+                        rule.by_day.push(parse_by_day(day).ok_or_else(|| format!("unsupported BYDAY '{}'", day))?);
+                    }
+                }
+                "BYMONTHDAY" => {
+                    for day in value.split(',') {
+                        let day: i32 = day.trim().parse().map_err(|_| format!("invalid BYMONTHDAY '{}'", day))?;
+                        if day == 0 || day.abs() > 31 {
+                            return Err(format!("invalid BYMONTHDAY '{}'", day));
+                        }
+                        rule.by_month_day.push(day);
+                    }
+                }
+                "BYMONTH" => {
+                    for month in value.split(',') {
+                        let month: u32 = month.trim().parse().map_err(|_| format!("invalid BYMONTH '{}'", month))?;
+                        if !(1..=12).contains(&month) {
+                            return Err(format!("invalid BYMONTH '{}'", month));
+                        }
+                        rule.by_month.push(month);
+                    }
+                }
+                "WKST" => {}
+                other => return Err(format!("unsupported RRULE part '{}'", other)),
+            }
+        }
+        rule.frequency = frequency.ok_or("RRULE has no FREQ")?;
+        let has_ordinals = rule.by_day.iter().any(|(ordinal, _)| ordinal.is_some());
+        if has_ordinals && matches!(rule.frequency, Frequency::Daily | Frequency::Weekly) {
+            return Err("BYDAY ordinals like 1MO need FREQ=MONTHLY or FREQ=YEARLY".to_string());
+        }
+        if has_ordinals && rule.frequency == Frequency::Yearly && rule.by_month.is_empty() {
+            return Err("yearly BYDAY ordinals need BYMONTH".to_string());
+        }
+        Ok(rule)
+    }
+
+    /// Occurrence starts in `[from, until)`, in order, skipping `exdates`.
+    pub fn starts_between(
+        &self,
+        dtstart: NaiveDateTime,
+        exdates: &[NaiveDateTime],
+        from: NaiveDateTime,
+        until: NaiveDateTime,
+    ) -> Vec<NaiveDateTime> {
+        // Without COUNT, periods before `from` never matter, so skip straight past them.
+        let first_step = match self.count {
+            Some(_) => 0,
+            None => self.periods_before(dtstart.date(), from.date()).saturating_sub(1),
+        };
+        let mut starts = Vec::new();
+        for (index, candidate) in self.candidates(dtstart, first_step).take(MAX_CANDIDATES).enumerate() {
+            if candidate >= until || self.until.is_some_and(|last| candidate > last) {
+                break;
+            }
+            // COUNT includes occurrences that EXDATE later removes (RFC 5545 3.8.5.1).
+            if self.count.is_some_and(|count| index as u32 >= count) {
+                break;
+            }
+            if candidate >= from && !exdates.contains(&candidate) {
+                starts.push(candidate);
+            }
+        }
+        starts
+    }
+
+    fn periods_before(&self, dtstart: NaiveDate, from: NaiveDate) -> u32 {
+        if from <= dtstart {
+            return 0;
+        }
+        let months = |a: NaiveDate, b: NaiveDate| (b.year() - a.year()) * 12 + b.month() as i32 - a.month() as i32;
+        let periods = match self.frequency {
+            Frequency::Daily => (from - dtstart).num_days(),
+            Frequency::Weekly => (from - dtstart).num_days() / 7,
+            Frequency::Monthly => months(dtstart, from) as i64,
+            Frequency::Yearly => (from.year() - dtstart.year()) as i64,
+        };
+        (periods / self.interval as i64).clamp(0, u32::MAX as i64) as u32
+    }
+
+    fn candidates(&self, dtstart: NaiveDateTime, first_step: u32) -> impl Iterator<Item = NaiveDateTime> + '_ {
+        let time = dtstart.time();
+        // Bounded in periods too: BYMONTH=2;BYMONTHDAY=30 never yields a candidate.
+        (first_step..first_step.saturating_add(MAX_CANDIDATES as u32))
+            .map_while(move |step| {
+                let n = step.checked_mul(self.interval)?;
+                self.period_dates(dtstart.date(), n)
+            })
+            .flatten()
+            .map(move |date| date.and_time(time))
+            .filter(move |start| *start >= dtstart)
+    }
+
+    /// The dates of the `n`th period after `dtstart`, in order. `None` once out of range.
+    fn period_dates(&self, dtstart: NaiveDate, n: u32) -> Option<Vec<NaiveDate>> {
+        let mut dates = match self.frequency {
+            Frequency::Daily => vec![dtstart.checked_add_days(Days::new(n as u64))?],
+            Frequency::Weekly => {
+                let monday = dtstart.checked_sub_days(Days::new(dtstart.weekday().num_days_from_monday() as u64))?;
+                let week = monday.checked_add_days(Days::new(7 * n as u64))?;
+                let weekdays: Vec<Weekday> = if self.by_day.is_empty() {
+                    vec![dtstart.weekday()]
+                } else {
+                    self.by_day.iter().map(|(_, day)| *day).collect()
+                };
+                weekdays
+                    .into_iter()
+                    .filter_map(|day| week.checked_add_days(Days::new(day.num_days_from_monday() as u64)))
+                    .collect()
+            }
+            Frequency::Monthly => {
+                let month = dtstart.with_day(1)?.checked_add_months(Months::new(n))?;
+                self.month_dates(month, dtstart.day())
+            }
+            Frequency::Yearly => {
+                let year = dtstart.with_day(1)?.with_month(1)?.checked_add_months(Months::new(12 * n))?;
+                let months = if self.by_month.is_empty() { vec![dtstart.month()] } else { self.by_month.clone() };
+                months
+                    .into_iter()
+                    .filter_map(|month| year.with_month(month))
+                    .flat_map(|month| self.month_dates(month, dtstart.day()))
+                    .collect()
+            }
+        };
+        // BYxxx parts that expand in coarser frequencies only filter in finer ones.
+        dates.retain(|date| {
+            (self.by_month.is_empty() || self.by_month.contains(&date.month()))
+                && (self.frequency != Frequency::Daily
+                    || ((self.by_day.is_empty() || self.by_day.iter().any(|(_, day)| *day == date.weekday()))
+                        && (self.by_month_day.is_empty() || month_day_matches(&self.by_month_day, *date))))
+        });
+        dates.sort();
+        dates.dedup();
+        Some(dates)
+    }
+
+    /// Days of `month` (its first day) picked by BYMONTHDAY and BYDAY, or `default_day`.
+    /// Months without that day (the 31st, Feb 29th) are skipped, as RFC 5545 requires.
+    fn month_dates(&self, month: NaiveDate, default_day: u32) -> Vec<NaiveDate> {
+        let days: Vec<NaiveDate> = (0..31)
+            .filter_map(|offset| month.checked_add_days(Days::new(offset)))
+            .take_while(|date| date.month() == month.month())
+            .collect();
+        if self.by_month_day.is_empty() && self.by_day.is_empty() {
+            return days.into_iter().filter(|date| date.day() == default_day).collect();
+        }
+        days.iter()
+            .copied()
+            .filter(|date| self.by_month_day.is_empty() || month_day_matches(&self.by_month_day, *date))
+            .filter(|date| {
+                self.by_day.is_empty()
+                    || self.by_day.iter().any(|&(ordinal, weekday)| {
+                        date.weekday() == weekday && ordinal.is_none_or(|ordinal| weekday_ordinal_matches(&days, *date, ordinal))
+                    })
+            })
+            .collect()
+    }
+}
+
+fn month_day_matches(by_month_day: &[i32], date: NaiveDate) -> bool {
+    let days_in_month = (28..=31)
+        .rev()
+        .find(|day| date.with_day(*day).is_some())
+        .unwrap_or(28) as i32;
+    by_month_day.iter().any(|&day| {
+        let day = if day < 0 { days_in_month + day + 1 } else { day };
+        day == date.day() as i32
+    })
+}
+
+/// Whether `date` is the `ordinal`th (or, if negative, from the end) of its weekday in `days`.
+fn weekday_ordinal_matches(days: &[NaiveDate], date: NaiveDate, ordinal: i32) -> bool {
+    let same: Vec<&NaiveDate> = days.iter().filter(|d| d.weekday() == date.weekday()).collect();
+    let index = if ordinal > 0 {
+        ordinal as usize - 1
+    } else {
+        match same.len().checked_sub(ordinal.unsigned_abs() as usize) {
+            Some(index) => index,
+            None => return false,
+        }
+    };
+    same.get(index).is_some_and(|d| **d == date)
+}
+
+fn parse_by_day(value: &str) -> Option<(Option<i32>, Weekday)> {
+    let value = value.trim();
+    let split = value.len().checked_sub(2)?;
+    let (ordinal, day) = value.split_at(split);
+    let ordinal = match ordinal {
+        "" => None,
+        ordinal => Some(ordinal.trim_start_matches('+').parse::<i32>().ok().filter(|n| *n != 0 && n.abs() <= 5)?),
+    };
+    Some((ordinal, parse_ics_weekday(day)?))
+}
+
+fn parse_ics_weekday(value: &str) -> Option<Weekday> {
+    match value.trim().to_ascii_uppercase().as_str() {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        other => weekday_from_str(other),
+    }
+}
+
+fn parse_until(value: &str, zone: Zone) -> Option<NaiveDateTime> {
+    if let Some(utc) = value.strip_suffix('Z') {
+        let utc = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some(zone.wall_time(Utc.from_utc_datetime(&utc).with_timezone(&Local)));
+    }
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .ok()
+        .or_else(|| NaiveDate::parse_from_str(value, "%Y%m%d").ok().map(|d| d.and_hms_opt(23, 59, 59).unwrap_or_default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn starts(rule: &str, dtstart: &str, exdates: &[&str], from: &str, until: &str) -> Vec<String> {
+        let exdates: Vec<NaiveDateTime> = exdates.iter().map(|d| at(d)).collect();
+        Recurrence::parse_rrule(rule, Zone::Local)
+            .unwrap()
+            .starts_between(at(dtstart), &exdates, at(from), at(until))
+            .iter()
+            .map(|start| start.format("%Y-%m-%d %H:%M").to_string())
+            .collect()
+    }
+
+    #[test]
+    fn first_monday_of_the_month() {
+        assert_eq!(
+            starts("FREQ=MONTHLY;BYDAY=1MO", "2026-01-01 08:00", &[], "2026-01-01 00:00", "2026-04-01 00:00"),
+            ["2026-01-05 08:00", "2026-02-02 08:00", "2026-03-02 08:00"]
+        );
+    }
+
+    #[test]
+    fn last_friday_and_negative_month_days() {
+        assert_eq!(
+            starts("FREQ=MONTHLY;BYDAY=-1FR", "2026-01-01 08:00", &[], "2026-09-01 00:00", "2026-11-01 00:00"),
+            ["2026-09-25 08:00", "2026-10-30 08:00"]
+        );
+        assert_eq!(
+            starts("FREQ=MONTHLY;BYMONTHDAY=-1", "2026-01-31 08:00", &[], "2026-02-01 00:00", "2026-04-01 00:00"),
+            ["2026-02-28 08:00", "2026-03-31 08:00"]
+        );
+    }
+
+    #[test]
+    fn count_includes_excluded_dates() {
+        assert_eq!(
+            starts(
+                "FREQ=DAILY;COUNT=3",
+                "2026-10-01 09:00",
+                &["2026-10-02 09:00"],
+                "2026-09-01 00:00",
+                "2026-11-01 00:00"
+            ),
+            ["2026-10-01 09:00", "2026-10-03 09:00"]
+        );
+    }
+
+    #[test]
+    fn until_is_inclusive() {
+        assert_eq!(
+            starts(
+                "FREQ=WEEKLY;BYDAY=MO,WE;UNTIL=20261014T090000",
+                "2026-10-05 09:00",
+                &[],
+                "2026-10-01 00:00",
+                "2026-11-01 00:00"
+            ),
+            ["2026-10-05 09:00", "2026-10-07 09:00", "2026-10-12 09:00", "2026-10-14 09:00"]
+        );
+    }
+
+    #[test]
+    fn utc_until_is_converted_to_the_zone() {
+        // 02:00 UTC is 10:00 in Shanghai, so the 09:00 start on the 14th still counts.
+        let rule = Recurrence::parse_rrule("FREQ=DAILY;UNTIL=20261014T020000Z", Zone::Named(chrono_tz::Asia::Shanghai))
+            .unwrap();
+        assert_eq!(rule.until, Some(at("2026-10-14 10:00")));
+        let starts = rule.starts_between(at("2026-10-12 09:00"), &[], at("2026-10-01 00:00"), at("2026-11-01 00:00"));
+        assert_eq!(starts, [at("2026-10-12 09:00"), at("2026-10-13 09:00"), at("2026-10-14 09:00")]);
+        let late = rule.starts_between(at("2026-10-12 10:30"), &[], at("2026-10-01 00:00"), at("2026-11-01 00:00"));
+        assert_eq!(late, [at("2026-10-12 10:30"), at("2026-10-13 10:30")]);
+    }
+
+    #[test]
+    fn skips_ahead_to_the_window() {
+        assert_eq!(
+            starts("FREQ=DAILY", "1970-01-01 12:00", &[], "2026-10-18 00:00", "2026-10-19 00:00"),
+            ["2026-10-18 12:00"]
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_rules() {
+        assert!(Recurrence::parse_rrule("INTERVAL=2", Zone::Local).is_err());
+        assert!(Recurrence::parse_rrule("FREQ=WEEKLY;BYDAY=1MO", Zone::Local).is_err());
+        assert!(Recurrence::parse_rrule("FREQ=YEARLY;BYDAY=1MO", Zone::Local).is_err());
+        assert!(Recurrence::parse_rrule("FREQ=MONTHLY;BYMONTHDAY=0", Zone::Local).is_err());
+        assert!(Recurrence::parse_rrule("FREQ=DAILY;BYSETPOS=1", Zone::Local).is_err());
+    }
+}
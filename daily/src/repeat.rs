@@ -0,0 +1,228 @@
+use crate::recurrence::{Frequency, Recurrence};
+use crate::timezone::Zone;
+use chrono::{Datelike, Days, NaiveDate, NaiveTime, Weekday};
+
+const MONTH_NAMES: [&str; 12] = ["JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC"];
+const WEEKDAY_NAMES: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+/// The days a special with `repeat` runs on. The time of day still comes from `start`
+/// and `end`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Repeat {
+    /// `FREQ=MONTHLY;BYDAY=1MO` and the rest of [`Recurrence`], counted from an anchor date.
+    Rule(Recurrence),
+    /// The day fields of a crontab line, `day-of-month month day-of-week`. `None` is `*`.
+    Cron {
+        month_days: Option<Vec<u32>>,
+        months: Vec<u32>,
+        weekdays: Option<Vec<Weekday>>,
+    },
+}
+
+impl Repeat {
+    /// Parses an RRULE, the three cron day fields (`* * 1-5`), or `daily`, `weekdays`
+    /// or `weekends`. An RRULE's UTC `UNTIL` is read in `zone`.
+    pub fn parse(value: &str, zone: Zone) -> Result<Repeat, String> {
+        let value = value.trim();
+        let upper = value.to_ascii_uppercase();
+        if upper.starts_with("FREQ=") || upper.starts_with("RRULE:") {
+            return Recurrence::parse_rrule(&upper, zone).map(Repeat::Rule);
+        }
+        let fields = match value.to_lowercase().as_str() {
+            "daily" => "* * *".to_string(),
+            "weekdays" => "* * 1-5".to_string(),
+            "weekends" => "* * 6,0".to_string(),
+            _ => value.to_string(),
+        };
+        let fields: Vec<&str> = fields.split_whitespace().collect();
+        let [month_days, months, weekdays] = fields[..] else {
+            return Err(format!(
+                "'{}' is not an RRULE (FREQ=...), 'daily', 'weekdays', 'weekends' or the cron day fields \
+                 'day-of-month month day-of-week'; the time of day goes in start and end",
+                value
+            ));
+        };
+        let month_days = (month_days != "*")
+            .then(|| parse_field(month_days, 1, 31, &[]))
+            .transpose()
+            .map_err(|e| format!("day-of-month: {}", e))?;
+        let months = parse_field(months, 1, 12, &MONTH_NAMES).map_err(|e| format!("month: {}", e))?;
+        let weekdays = (weekdays != "*")
+            .then(|| parse_field(weekdays, 0, 7, &WEEKDAY_NAMES))
+            .transpose()
+            .map_err(|e| format!("day-of-week: {}", e))?
+            .map(|days| days.into_iter().map(cron_weekday).collect());
+        Ok(Repeat::Cron {
+            month_days,
+            months,
+            weekdays,
+        })
+    }
+
+    /// Whether an occurrence starts on `date`. Cron weekdays use `weekday`, the weekday
+    /// the holiday calendar says `date` behaves as; RRULEs follow the calendar date and
+    /// count INTERVAL and COUNT from `anchor`.
+    pub fn matches(&self, anchor: NaiveDate, date: NaiveDate, weekday: Weekday) -> bool {
+        match self {
+            Repeat::Rule(rule) => {
+                let Some(next) = date.checked_add_days(Days::new(1)) else {
+                    return false;
+                };
+                let midnight = |d: NaiveDate| d.and_time(NaiveTime::MIN);
+                !rule
+                    .starts_between(midnight(anchor), &[], midnight(date), midnight(next))
+                    .is_empty()
+            }
+            Repeat::Cron {
+                month_days,
+                months,
+                weekdays,
+            } => {
+                if !months.contains(&date.month()) {
+                    return false;
+                }
+                let day_matches = month_days.as_ref().map(|days| days.contains(&date.day()));
+                let weekday_matches = weekdays.as_ref().map(|days| days.contains(&weekday));
+                // Like cron: with both fields restricted, either one is enough.
+                match (day_matches, weekday_matches) {
+                    (Some(day), Some(weekday)) => day || weekday,
+                    (Some(day), None) => day,
+                    (None, Some(weekday)) => weekday,
+                    (None, None) => true,
+                }
+            }
+        }
+    }
+
+    /// Whether `matches` depends on `anchor`, so the special should set `from`.
+    pub fn needs_anchor(&self) -> bool {
+        match self {
+            Repeat::Rule(rule) => {
+                rule.interval > 1
+                    || rule.count.is_some()
+                    || (rule.by_day.is_empty() && rule.by_month_day.is_empty() && rule.frequency != Frequency::Daily)
+            }
+            Repeat::Cron { .. } => false,
+        }
+    }
+}
+
+/// `*`, `*/n`, `a`, `a-b`, `a-b/n` and comma lists of them, with optional names.
+fn parse_field(value: &str, min: u32, max: u32, names: &[&str]) -> Result<Vec<u32>, String> {
+    let number = |part: &str| -> Result<u32, String> {
+        let upper = part.to_ascii_uppercase();
+        let parsed = match names.iter().position(|name| *name == upper) {
+            Some(index) => index as u32 + min,
+            None => part.parse().map_err(|_| format!("'{}' is not a number", part))?,
+        };
+        if parsed < min || parsed > max {
+            return Err(format!("{} is outside {}-{}", parsed, min, max));
+        }
+        Ok(parsed)
+    };
+    let mut values = Vec::new();
+    for item in value.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("invalid step '{}'", step))?,
+            ),
+            None => (item, 1),
+        };
+        let (first, last) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((first, last)) => (number(first)?, number(last)?),
+                // `5/2` means from 5 to the end, as in Vixie cron.
+                None if step > 1 => (number(range)?, max),
+                None => {
+                    let single = number(range)?;
+                    (single, single)
+                }
+            },
+        };
+        if last < first {
+            return Err(format!("range '{}' ends before it starts", range));
+        }
+        values.extend((first..=last).step_by(step as usize));
+    }
+    values.sort_unstable();
+    values.dedup();
+    Ok(values)
+}
+
+/// Cron counts weekdays from Sunday = 0, and accepts 7 for Sunday too.
+fn cron_weekday(day: u32) -> Weekday {
+    match day % 7 {
+        0 => Weekday::Sun,
+        1 => Weekday::Mon,
+        2 => Weekday::Tue,
+        3 => Weekday::Wed,
+        4 => Weekday::Thu,
+        5 => Weekday::Fri,
+        _ => Weekday::Sat,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    fn matches(repeat: &str, value: &str) -> bool {
+        let day = date(value);
+        Repeat::parse(repeat, Zone::Local).unwrap().matches(date("2026-01-01"), day, day.weekday())
+    }
+
+    #[test]
+    fn cron_fields() {
+        assert!(matches("* * 1-5", "2026-10-19"));
+        assert!(!matches("* * 1-5", "2026-10-18"));
+        assert!(matches("* * SUN", "2026-10-18"));
+        assert!(matches("* * 7", "2026-10-18"));
+        assert!(matches("1 */3 *", "2026-10-01"));
+        assert!(!matches("1 */3 *", "2026-11-01"));
+        assert!(matches("weekends", "2026-10-17"));
+        assert!(!matches("weekdays", "2026-10-17"));
+    }
+
+    #[test]
+    fn cron_day_of_month_or_weekday() {
+        // 2026-10-15 is a Thursday, 2026-10-19 a Monday.
+        assert!(matches("15 * MON", "2026-10-15"));
+        assert!(matches("15 * MON", "2026-10-19"));
+        assert!(!matches("15 * MON", "2026-10-20"));
+    }
+
+    #[test]
+    fn cron_uses_the_effective_weekday() {
+        let repeat = Repeat::parse("* * 1-5", Zone::Local).unwrap();
+        // A make-up workday on a Sunday behaves as a Monday.
+        assert!(repeat.matches(date("2026-01-01"), date("2026-09-20"), Weekday::Mon));
+    }
+
+    #[test]
+    fn rrule_counts_from_the_anchor() {
+        let repeat = Repeat::parse("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO", Zone::Local).unwrap();
+        let anchor = date("2026-10-05");
+        assert!(repeat.matches(anchor, date("2026-10-19"), Weekday::Mon));
+        assert!(!repeat.matches(anchor, date("2026-10-12"), Weekday::Mon));
+        assert!(repeat.needs_anchor());
+        assert!(!Repeat::parse("FREQ=MONTHLY;BYDAY=1MO", Zone::Local).unwrap().needs_anchor());
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        assert!(Repeat::parse("0 9 * * 1", Zone::Local).is_err());
+        assert!(Repeat::parse("32 * *", Zone::Local).is_err());
+        assert!(Repeat::parse("* * 5-1", Zone::Local).is_err());
+        assert!(Repeat::parse("* */0 *", Zone::Local).is_err());
+        assert!(Repeat::parse("FREQ=HOURLY", Zone::Local).is_err());
+    }
+}
@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// Priorities the `wallpapers` layout translates to. A temp wallpaper always wins.
+pub const TEMP_PRIORITY: i32 = i32::MAX;
+pub const SPECIAL_PRIORITY: i32 = 300;
+pub const CALENDAR_PRIORITY: i32 = 300;
+pub const DATE_PRIORITY: i32 = 200;
+pub const DAY_PRIORITY: i32 = 100;
+
+/// A wallpaper shown while every condition holds. Among all matching rules and
+/// `wallpapers` entries the highest priority wins; ties go to the `wallpapers` entry.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Rule {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    pub priority: i32,
+    /// Weekday names; holidays and make-up workdays count as the weekday they behave as.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub weekdays: Vec<String>,
+    /// Any `wallpapers.dates` key form, including ranges and `term:<name>`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dates: Vec<String>,
+    /// `HH:MM` or a sun time like `sunset-30m`. An `end` at or before `start` is on the next day, and the weekday and
+    /// date conditions then apply to the day the window started.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+    /// Machine names, compared case-insensitively.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hostnames: Vec<String>,
+    /// Term weeks like `1-8,10`, counted from `terms` or `wallpapers.term_start`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weeks: Option<String>,
+    /// Overrides the config's `timezone` for this rule.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    pub url: String,
+}
+
+impl Rule {
+    pub fn label(&self, index: usize) -> String {
+        if self.name.is_empty() {
+            format!("rules[{}]", index)
+        } else {
+            format!("rules[{}] \"{}\"", index, self.name)
+        }
+    }
+}
+
+/// This machine's name, looked up once.
+pub fn hostname() -> &'static str {
+    static HOSTNAME: OnceLock<String> = OnceLock::new();
+    HOSTNAME.get_or_init(|| sysinfo::System::host_name().unwrap_or_default())
+}
@@ -0,0 +1,990 @@
+use crate::calendars;
+use crate::config::{Config, SpecialPeriod};
+use crate::dates::{matching_keys, DateKey};
+use crate::holidays::{self, DayKind};
+use crate::repeat::Repeat;
+use crate::rules::{self, Rule, CALENDAR_PRIORITY, DATE_PRIORITY, DAY_PRIORITY, SPECIAL_PRIORITY, TEMP_PRIORITY};
+use crate::state::TempWallpaper;
+use crate::terms::{self, TermPosition};
+use crate::solar::{self, TimeSpec};
+use crate::timezone::Zone;
+use chrono::{DateTime, Datelike, Days, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Weekday};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MatchedRule {
+    Temp { expiry: DateTime<Local> },
+    Special {
+        day: String,
+        start: String,
+        end: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        end_day: Option<String>,
+    },
+    Date { key: String },
+    Day { weekday: String },
+    Rule { name: String, priority: i32 },
+}
+
+impl fmt::Display for MatchedRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatchedRule::Temp { expiry } => write!(f, "temp until {}", expiry.format("%Y-%m-%d %H:%M")),
+            MatchedRule::Special {
+                day,
+                start,
+                end,
+                end_day: Some(end_day),
+            } => write!(f, "special {} {}-{} {}", day, start, end_day, end),
+            MatchedRule::Special { day, start, end, .. } => write!(f, "special {} {}-{}", day, start, end),
+            MatchedRule::Date { key } => write!(f, "date {}", key),
+            MatchedRule::Day { weekday } => write!(f, "day {}", weekday),
+            MatchedRule::Rule { name, priority } => write!(f, "{} (priority {})", name, priority),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum SkipReason {
+    NotSet,
+    Expired { expiry: DateTime<Local> },
+    UnknownWeekday { day: String },
+    UnparsableTime { value: String },
+    WrongWeekday { day: String, today: String },
+    OutsideTimeWindow { start: String, end: String, time: String },
+    NoEntry { key: String },
+    NotInDates { date: String },
+    OutsideDateBounds { from: Option<String>, until: Option<String>, date: String },
+    WrongWeek { week: i64, rule: String },
+    NoTermWeek { date: String },
+    WrongTermPeriod { during: String, position: String },
+    InvalidScope { field: String, value: String },
+    Holiday { name: String },
+    NotRepeatDay { repeat: String, date: String },
+    WrongHost { hostnames: String, host: String },
+    NoLocation,
+    NoSolarEvent { date: String },
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::NotSet => write!(f, "not set"),
+            SkipReason::Expired { expiry } => write!(f, "expired at {}", expiry.format("%Y-%m-%d %H:%M")),
+            SkipReason::UnknownWeekday { day } => write!(f, "unknown weekday '{}'", day),
+            SkipReason::UnparsableTime { value } => write!(f, "unparsable time '{}'", value),
+            SkipReason::WrongWeekday { day, today } => write!(f, "only on {}, today is {}", day, today),
+            SkipReason::OutsideTimeWindow { start, end, time } => write!(f, "{} is outside {}-{}", time, start, end),
+            SkipReason::NoEntry { key } => write!(f, "no entry for {}", key),
+            SkipReason::NotInDates { date } => write!(f, "{} is not one of its dates", date),
+            SkipReason::OutsideDateBounds { from, until, date } => write!(
+                f,
+                "{} is outside {}..{}",
+                date,
+                from.as_deref().unwrap_or(""),
+                until.as_deref().unwrap_or("")
+            ),
+            SkipReason::WrongWeek { week, rule } => write!(f, "term week {} is not {}", week, rule),
+            SkipReason::NoTermWeek { date } => {
+                write!(f, "{} has no term week (set terms or wallpapers.term_start)", date)
+            }
+            SkipReason::WrongTermPeriod { during, position } => write!(f, "only during {}, now {}", during, position),
+            SkipReason::Holiday { name } => write!(f, "holiday: {}", name),
+            SkipReason::InvalidScope { field, value } => write!(f, "invalid {} '{}'", field, value),
+            SkipReason::NotRepeatDay { repeat, date } => write!(f, "{} does not match '{}'", date, repeat),
+            SkipReason::WrongHost { hostnames, host } => write!(f, "only on {}, this machine is {}", hostnames, host),
+            SkipReason::NoLocation => write!(f, "sun times need location in the config"),
+            SkipReason::NoSolarEvent { date } => write!(f, "the sun does not rise or set that way on {}", date),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum RuleCheck {
+    Selected,
+    /// Matched, but an earlier rule had already been selected.
+    Shadowed,
+    Skipped(SkipReason),
+}
+
+impl fmt::Display for RuleCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleCheck::Selected => write!(f, "selected"),
+            RuleCheck::Shadowed => write!(f, "matched, but an earlier rule wins"),
+            RuleCheck::Skipped(reason) => write!(f, "skipped: {}", reason),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceEntry {
+    pub rule: String,
+    pub priority: i32,
+    pub url: Option<String>,
+    #[serde(flatten)]
+    pub check: RuleCheck,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Decision {
+    pub at: DateTime<Local>,
+    pub url: Option<String>,
+    pub rule: Option<MatchedRule>,
+    pub trace: Vec<TraceEntry>,
+}
+
+impl Decision {
+    fn offer(&mut self, priority: i32, rule: String, url: &str, matched: MatchedRule) {
+        let check = if self.rule.is_none() {
+            self.url = Some(url.to_string());
+            self.rule = Some(matched);
+            RuleCheck::Selected
+        } else {
+            RuleCheck::Shadowed
+        };
+        self.trace.push(TraceEntry {
+            rule,
+            priority,
+            url: Some(url.to_string()),
+            check,
+        });
+    }
+
+    fn skip(&mut self, priority: i32, rule: String, url: Option<&str>, reason: SkipReason) {
+        self.trace.push(TraceEntry {
+            rule,
+            priority,
+            url: url.map(str::to_string),
+            check: RuleCheck::Skipped(reason),
+        });
+    }
+}
+
+/// Picks the wallpaper for `now` without touching any shared state.
+pub fn resolve(
+    config: Option<&Config>,
+    temp: Option<&TempWallpaper>,
+    now: DateTime<Local>,
+) -> Option<(String, MatchedRule)> {
+    let decision = explain(config, temp, now);
+    decision.url.zip(decision.rule)
+}
+
+/// A rule checked by `explain`, before priorities put it in order.
+struct Candidate {
+    priority: i32,
+    rule: String,
+    url: Option<String>,
+    outcome: Result<MatchedRule, SkipReason>,
+}
+
+impl Candidate {
+    fn new(priority: i32, rule: String, url: Option<&str>, outcome: Result<MatchedRule, SkipReason>) -> Candidate {
+        Candidate {
+            priority,
+            rule,
+            url: url.map(str::to_string),
+            outcome,
+        }
+    }
+}
+
+/// Checks every rule, highest priority first, and records why each one was selected,
+/// shadowed or skipped. The `wallpapers` layout translates to fixed priorities
+/// (temp > special = calendar > date > weekday); `rules` slot in anywhere.
+pub fn explain(config: Option<&Config>, temp: Option<&TempWallpaper>, now: DateTime<Local>) -> Decision {
+    let mut candidates = Vec::new();
+    match temp {
+        Some(temp) if now < temp.expiry => candidates.push(Candidate::new(
+            TEMP_PRIORITY,
+            "temp".to_string(),
+            Some(&temp.url),
+            Ok(MatchedRule::Temp { expiry: temp.expiry }),
+        )),
+        Some(temp) => candidates.push(Candidate::new(
+            TEMP_PRIORITY,
+            "temp".to_string(),
+            Some(&temp.url),
+            Err(SkipReason::Expired { expiry: temp.expiry }),
+        )),
+        None => candidates.push(Candidate::new(TEMP_PRIORITY, "temp".to_string(), None, Err(SkipReason::NotSet))),
+    }
+    if let Some(config) = config {
+        collect_candidates(config, now, &mut candidates);
+    }
+    // Stable, so equal priorities keep the order they were collected in.
+    candidates.sort_by_key(|candidate| Reverse(candidate.priority));
+
+    let mut decision = Decision {
+        at: now,
+        url: None,
+        rule: None,
+        trace: Vec::new(),
+    };
+    for candidate in candidates {
+        match (candidate.outcome, candidate.url) {
+            (Ok(matched), Some(url)) => decision.offer(candidate.priority, candidate.rule, &url, matched),
+            (Ok(_), None) => {}
+            (Err(reason), url) => decision.skip(candidate.priority, candidate.rule, url.as_deref(), reason),
+        }
+    }
+    decision
+}
+
+fn collect_candidates(config: &Config, now: DateTime<Local>, candidates: &mut Vec<Candidate>) {
+    let today = Zone::of_config(config).wall_time(now).date();
+    // Holidays and make-up workdays decide which weekday's rules apply.
+    let current_weekday = holidays::effective_weekday(config, today);
+    for (i, period) in config.wallpapers.specials.iter().enumerate() { //
+        let zone = Zone::of_rule(config, period.timezone.as_deref());
+        let outcome = check_special(period, config, zone, zone.wall_time(now)).map(|()| MatchedRule::Special {
+            day: period.repeat.clone().unwrap_or_else(|| period.day.clone()),
+            start: period.start.clone(),
+            end: period.end.clone(),
+            end_day: period.end_day.clone(),
+        });
+        candidates.push(Candidate::new(SPECIAL_PRIORITY, format!("specials[{}]", i), Some(&period.url), outcome));
+    }
+    // Event times are wall times in the config's zone (UTC ones were converted on load).
+    for (i, event, start, end) in calendars::active_at(config, Zone::of_config(config).wall_time(now)) {
+        let matched = MatchedRule::Special {
+            day: weekday_name(start.weekday()).to_string(),
+            start: start.format("%H:%M").to_string(),
+            end: end.format("%H:%M").to_string(),
+            end_day: (end.date() != start.date()).then(|| weekday_name(end.weekday()).to_string()),
+        };
+        let rule = format!("calendars[{}] \"{}\"", i, event.summary);
+        candidates.push(Candidate::new(CALENDAR_PRIORITY, rule, Some(&event.target), Ok(matched)));
+    }
+
+    let date_keys = matching_keys(config.wallpapers.dates.keys(), today, &config.terms);
+    if date_keys.is_empty() {
+        let key = today.format("%Y-%m-%d").to_string();
+        candidates.push(Candidate::new(DATE_PRIORITY, "dates".to_string(), None, Err(SkipReason::NoEntry { key })));
+    }
+    for key in date_keys {
+        let rule = format!("dates[\"{}\"]", key);
+        let matched = MatchedRule::Date { key: key.clone() };
+        candidates.push(Candidate::new(DATE_PRIORITY, rule, Some(&config.wallpapers.dates[key]), Ok(matched)));
+    }
+
+    let weekday = weekday_name(current_weekday).to_string();
+    let (key, day_url) = match config.wallpapers.days.lookup(current_weekday) {
+        Some((key, url)) if key == weekday => (key.to_string(), Some(url)),
+        Some((key, url)) => (format!("{} ({})", key, weekday), Some(url)),
+        None => (weekday.clone(), None),
+    };
+    let rule = match holidays::day_kind(config, today) {
+        DayKind::Regular => format!("days.{}", key),
+        kind => format!("days.{} ({})", key, kind),
+    };
+    let outcome = match day_url {
+        Some(_) => Ok(MatchedRule::Day { weekday }),
+        None => Err(SkipReason::NoEntry { key: weekday }),
+    };
+    candidates.push(Candidate::new(DAY_PRIORITY, rule, day_url, outcome));
+
+    for (i, rule) in config.rules.iter().enumerate() {
+        let name = rule.label(i);
+        let zone = Zone::of_rule(config, rule.timezone.as_deref());
+        let outcome = check_rule(rule, config, zone, zone.wall_time(now)).map(|()| MatchedRule::Rule {
+            name: name.clone(),
+            priority: rule.priority,
+        });
+        candidates.push(Candidate::new(rule.priority, name, Some(&rule.url), outcome));
+    }
+}
+
+fn check_rule(rule: &Rule, config: &Config, zone: Zone, now: NaiveDateTime) -> Result<(), SkipReason> {
+    if !rule.hostnames.is_empty() {
+        let host = rules::hostname();
+        if !rule.hostnames.iter().any(|name| name.eq_ignore_ascii_case(host)) {
+            return Err(SkipReason::WrongHost {
+                hostnames: rule.hostnames.join(", "),
+                host: host.to_string(),
+            });
+        }
+    }
+    let mut date = now.date();
+    if let Some((start, end)) = rule_times(rule)? {
+        // Conditions apply to the day the window started, which may be yesterday.
+        let mut started = None;
+        for day in [Some(date), date.pred_opt()].into_iter().flatten() {
+            if let Some((from, until)) = day_window(start, end, config, zone, day)?
+                && from <= now
+                && now < until
+            {
+                started = Some(day);
+                break;
+            }
+        }
+        date = match started {
+            Some(day) => day,
+            None => return Err(outside_window(start, end, config, zone, now)?),
+        };
+    }
+    if !rule.weekdays.is_empty() {
+        let weekday = holidays::effective_weekday(config, date);
+        if !rule.weekdays.iter().any(|day| weekday_from_str(day) == Some(weekday)) {
+            return Err(SkipReason::WrongWeekday {
+                day: rule.weekdays.join(", "),
+                today: weekday_name(weekday).to_string(),
+            });
+        }
+    }
+    if !rule.dates.is_empty() && matching_keys(rule.dates.iter(), date, &config.terms).is_empty() {
+        return Err(SkipReason::NotInDates {
+            date: date.format("%Y-%m-%d").to_string(),
+        });
+    }
+    if let Some(weeks) = &rule.weeks {
+        let position = terms::locate(&config.terms, date);
+        let week = scope_week(config, position.as_ref(), date)?;
+        check_weeks(weeks, week)?;
+    }
+    Ok(())
+}
+
+/// A rule's `start` and `end`, which must come together.
+fn rule_times(rule: &Rule) -> Result<Option<(&str, &str)>, SkipReason> {
+    match (&rule.start, &rule.end) {
+        (Some(start), Some(end)) => Ok(Some((start, end))),
+        (None, None) => Ok(None),
+        (Some(value), None) | (None, Some(value)) => Err(SkipReason::InvalidScope {
+            field: "start/end".to_string(),
+            value: value.clone(),
+        }),
+    }
+}
+
+fn check_special(period: &SpecialPeriod, config: &Config, zone: Zone, now: NaiveDateTime) -> Result<(), SkipReason> {
+    if is_dated(period) {
+        return check_dated(period, config, zone, now);
+    }
+    let window = special_window(period)?;
+    let (weekday, time) = (holidays::effective_weekday(config, now.date()), now.time());
+    let minute = week_minute(weekday, time);
+    if window.contains(minute) {
+        // Scopes apply to the date the occurrence started on, not today.
+        let since_start = (minute + MINUTES_PER_WEEK - window.start) % MINUTES_PER_WEEK;
+        let started = now - Duration::minutes(since_start as i64);
+        if let DayKind::Holiday { name } = holidays::day_kind(config, started.date()) {
+            return Err(SkipReason::Holiday { name });
+        }
+        return check_scope(period, config, started.date());
+    }
+    let start_day = window.start / MINUTES_PER_DAY;
+    if window.is_single_day() {
+        if start_day != weekday.num_days_from_monday() {
+            return Err(SkipReason::WrongWeekday {
+                day: period.day.clone(),
+                today: weekday_name(weekday).to_string(),
+            });
+        }
+        return Err(SkipReason::OutsideTimeWindow {
+            start: period.start.clone(),
+            end: period.end.clone(),
+            time: time.format("%H:%M").to_string(),
+        });
+    }
+    Err(SkipReason::OutsideTimeWindow {
+        start: week_minute_label(window.start),
+        end: week_minute_label(window.end()),
+        time: format!("{} {}", weekday_name(weekday), time.format("%H:%M")),
+    })
+}
+
+/// Whether a special is checked date by date rather than as a weekly window: `repeat`
+/// specials, and ones whose times follow the sun.
+pub fn is_dated(period: &SpecialPeriod) -> bool {
+    let solar = |value: &str| TimeSpec::parse(value).is_ok_and(|spec| spec.is_solar());
+    period.repeat.is_some() || solar(&period.start) || solar(&period.end)
+}
+
+fn check_dated(period: &SpecialPeriod, config: &Config, zone: Zone, now: NaiveDateTime) -> Result<(), SkipReason> {
+    let runs_on = dated_days(period, config)?;
+    let today = now.date();
+    // An occurrence that crosses midnight may have started yesterday.
+    for date in [Some(today), today.pred_opt()].into_iter().flatten() {
+        if runs_on(date)
+            && let Some((start, end)) = day_window(&period.start, &period.end, config, zone, date)?
+            && start <= now
+            && now < end
+        {
+            if let DayKind::Holiday { name } = holidays::day_kind(config, date) {
+                return Err(SkipReason::Holiday { name });
+            }
+            return check_scope(period, config, date);
+        }
+    }
+    if !runs_on(today) {
+        return Err(match &period.repeat {
+            Some(repeat) => SkipReason::NotRepeatDay {
+                repeat: repeat.clone(),
+                date: today.format("%Y-%m-%d").to_string(),
+            },
+            None => SkipReason::WrongWeekday {
+                day: period.day.clone(),
+                today: weekday_name(holidays::effective_weekday(config, today)).to_string(),
+            },
+        });
+    }
+    Err(outside_window(&period.start, &period.end, config, zone, now)?)
+}
+
+/// Which days a dated special starts on: its `repeat`, else its `day`.
+fn dated_days<'a>(period: &'a SpecialPeriod, config: &'a Config) -> Result<impl Fn(NaiveDate) -> bool + 'a, SkipReason> {
+    let zone = Zone::of_rule(config, period.timezone.as_deref());
+    let repeat = period.repeat.as_deref().map(|value| parse_repeat(value, zone)).transpose()?;
+    let anchor = repeat_anchor(period, config)?;
+    let day = match &repeat {
+        Some(_) => None,
+        None => Some(weekday_from_str(&period.day).ok_or_else(|| SkipReason::UnknownWeekday {
+            day: period.day.clone(),
+        })?),
+    };
+    Ok(move |date: NaiveDate| {
+        let weekday = holidays::effective_weekday(config, date);
+        match &repeat {
+            Some(repeat) => repeat.matches(anchor, date, weekday),
+            None => day == Some(weekday),
+        }
+    })
+}
+
+fn parse_repeat(value: &str, zone: Zone) -> Result<Repeat, SkipReason> {
+    Repeat::parse(value, zone).map_err(|_| SkipReason::InvalidScope {
+        field: "repeat".to_string(),
+        value: value.to_string(),
+    })
+}
+
+// A Monday, so weekly rules without BYDAY fall on Mondays.
+const REPEAT_EPOCH: Option<NaiveDate> = NaiveDate::from_ymd_opt(2024, 1, 1);
+
+/// RRULE INTERVAL and COUNT count from `from`, then `term_start`, then a fixed Monday.
+fn repeat_anchor(period: &SpecialPeriod, config: &Config) -> Result<NaiveDate, SkipReason> {
+    match (&period.from, &config.wallpapers.term_start) {
+        (Some(from), _) => parse_scope_date("from", from),
+        (None, Some(term_start)) => parse_scope_date("term_start", term_start),
+        (None, None) => Ok(REPEAT_EPOCH.unwrap_or_default()),
+    }
+}
+
+/// Wall-clock start and end of an occurrence starting on `date`, or `None` when a solar
+/// time does not happen that day. An end at or before the start is on the next day.
+fn day_window(
+    start: &str,
+    end: &str,
+    config: &Config,
+    zone: Zone,
+    date: NaiveDate,
+) -> Result<Option<(NaiveDateTime, NaiveDateTime)>, SkipReason> {
+    let (start, end) = (parse_time_spec(start)?, parse_time_spec(end)?);
+    let Some(start_at) = time_on(start, config, zone, date)? else {
+        return Ok(None);
+    };
+    let end_at = match time_on(end, config, zone, date)? {
+        Some(end_at) if end_at > start_at => Some(end_at),
+        _ => match date.succ_opt() {
+            Some(next) => time_on(end, config, zone, next)?,
+            None => None,
+        },
+    };
+    Ok(end_at.map(|end_at| (start_at, end_at)))
+}
+
+/// The wall time `spec` refers to on `date`.
+fn time_on(spec: TimeSpec, config: &Config, zone: Zone, date: NaiveDate) -> Result<Option<NaiveDateTime>, SkipReason> {
+    match spec {
+        TimeSpec::Clock(time) => Ok(Some(date.and_time(time))),
+        TimeSpec::Solar { event, offset } => {
+            let location = config.location.ok_or(SkipReason::NoLocation)?;
+            Ok(solar::event_time(event, date, location).map(|at| zone.wall_time(at.with_timezone(&Local)) + offset))
+        }
+    }
+}
+
+/// Why `now` is not in today's window: outside it, or the sun does not rise or set today.
+fn outside_window(start: &str, end: &str, config: &Config, zone: Zone, now: NaiveDateTime) -> Result<SkipReason, SkipReason> {
+    let label = |value: &str, at: NaiveDateTime| match parse_time_spec(value) {
+        Ok(TimeSpec::Solar { .. }) => format!("{} ({})", value, at.format("%H:%M")),
+        _ => value.to_string(),
+    };
+    Ok(match day_window(start, end, config, zone, now.date())? {
+        Some((from, until)) => SkipReason::OutsideTimeWindow {
+            start: label(start, from),
+            end: label(end, until),
+            time: now.format("%H:%M").to_string(),
+        },
+        None => SkipReason::NoSolarEvent {
+            date: now.format("%Y-%m-%d").to_string(),
+        },
+    })
+}
+
+fn parse_time_spec(value: &str) -> Result<TimeSpec, SkipReason> {
+    TimeSpec::parse(value).map_err(|_| SkipReason::UnparsableTime {
+        value: value.to_string(),
+    })
+}
+
+fn check_scope(period: &SpecialPeriod, config: &Config, date: NaiveDate) -> Result<(), SkipReason> {
+    let date_label = date.format("%Y-%m-%d").to_string();
+    if !period.dates.is_empty() && matching_keys(period.dates.iter(), date, &config.terms).is_empty() {
+        return Err(SkipReason::NotInDates { date: date_label });
+    }
+
+    let from = period.from.as_deref().map(|from| parse_scope_date("from", from)).transpose()?;
+    let until = period.until.as_deref().map(|until| parse_scope_date("until", until)).transpose()?;
+    if from.is_some_and(|from| date < from) || until.is_some_and(|until| date > until) {
+        return Err(SkipReason::OutsideDateBounds {
+            from: period.from.clone(),
+            until: period.until.clone(),
+            date: date_label,
+        });
+    }
+
+    let position = terms::locate(&config.terms, date);
+    if let Some(position) = &position {
+        let during = period.during.as_deref().unwrap_or("term");
+        let matches = match during {
+            "term" => matches!(position, TermPosition::InTerm { .. }),
+            "break" => !matches!(position, TermPosition::InTerm { .. }),
+            "always" => true,
+            _ => {
+                return Err(SkipReason::InvalidScope {
+                    field: "during".to_string(),
+                    value: during.to_string(),
+                })
+            }
+        };
+        if !matches {
+            return Err(SkipReason::WrongTermPeriod {
+                during: during.to_string(),
+                position: position.to_string(),
+            });
+        }
+    }
+
+    if period.every_n_weeks.is_none() && period.week_parity.is_none() && period.weeks.is_none() {
+        return Ok(());
+    }
+    let week = scope_week(config, position.as_ref(), date)?;
+    if let Some(weeks) = &period.weeks {
+        check_weeks(weeks, week)?;
+    }
+    if let Some(n) = period.every_n_weeks {
+        if n == 0 {
+            return Err(SkipReason::InvalidScope {
+                field: "every_n_weeks".to_string(),
+                value: n.to_string(),
+            });
+        }
+        if (week - 1).rem_euclid(n as i64) != 0 {
+            return Err(SkipReason::WrongWeek {
+                week,
+                rule: format!("every {} weeks", n),
+            });
+        }
+    }
+    if let Some(parity) = &period.week_parity {
+        let odd = match parity.to_lowercase().as_str() {
+            "odd" => true,
+            "even" => false,
+            _ => {
+                return Err(SkipReason::InvalidScope {
+                    field: "week_parity".to_string(),
+                    value: parity.clone(),
+                })
+            }
+        };
+        if (week.rem_euclid(2) == 1) != odd {
+            return Err(SkipReason::WrongWeek {
+                week,
+                rule: format!("an {} week", parity.to_lowercase()),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Weeks come from `terms` when the date is in one, otherwise from `term_start`.
+fn scope_week(config: &Config, position: Option<&TermPosition>, date: NaiveDate) -> Result<i64, SkipReason> {
+    match (position.and_then(TermPosition::week), &config.wallpapers.term_start) {
+        (Some(week), _) => Ok(week),
+        (None, Some(term_start)) => Ok(terms::calendar_week(parse_scope_date("term_start", term_start)?, date)),
+        (None, None) => Err(SkipReason::NoTermWeek {
+            date: date.format("%Y-%m-%d").to_string(),
+        }),
+    }
+}
+
+fn check_weeks(weeks: &str, week: i64) -> Result<(), SkipReason> {
+    let ranges = terms::parse_weeks(weeks).map_err(|_| SkipReason::InvalidScope {
+        field: "weeks".to_string(),
+        value: weeks.to_string(),
+    })?;
+    if !ranges.iter().any(|&(from, to)| from <= week && week <= to) {
+        return Err(SkipReason::WrongWeek {
+            week,
+            rule: format!("in weeks {}", weeks),
+        });
+    }
+    Ok(())
+}
+
+fn parse_scope_date(field: &str, value: &str) -> Result<NaiveDate, SkipReason> {
+    match DateKey::parse(value) {
+        Ok(DateKey::Fixed { start, end }) if start == end => Ok(start),
+        _ => Err(SkipReason::InvalidScope {
+            field: field.to_string(),
+            value: value.to_string(),
+        }),
+    }
+}
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+const MINUTES_PER_WEEK: u32 = 7 * MINUTES_PER_DAY;
+
+/// A special period as minutes since Monday 00:00; it may wrap past Sunday midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpecialWindow {
+    pub start: u32,
+    pub len: u32,
+}
+
+impl SpecialWindow {
+    pub fn contains(&self, minute: u32) -> bool {
+        (minute + MINUTES_PER_WEEK - self.start) % MINUTES_PER_WEEK < self.len
+    }
+
+    pub fn overlaps(&self, other: &SpecialWindow) -> bool {
+        self.len > 0 && other.len > 0 && (self.contains(other.start) || other.contains(self.start))
+    }
+
+    pub fn end(&self) -> u32 {
+        (self.start + self.len) % MINUTES_PER_WEEK
+    }
+
+    /// Starts and ends on the same day (ending at midnight counts).
+    pub fn is_single_day(&self) -> bool {
+        self.start % MINUTES_PER_DAY + self.len <= MINUTES_PER_DAY
+    }
+}
+
+pub fn special_window(period: &SpecialPeriod) -> Result<SpecialWindow, SkipReason> {
+    let day = weekday_from_str(&period.day).ok_or_else(|| SkipReason::UnknownWeekday {
+        day: period.day.clone(),
+    })?;
+    let start = parse_time(&period.start)?;
+    let end = parse_time(&period.end)?;
+    let end_day = match &period.end_day {
+        Some(end_day) => weekday_from_str(end_day).ok_or_else(|| SkipReason::UnknownWeekday {
+            day: end_day.clone(),
+        })?,
+        None if end > start => day,
+        None => day.succ(),
+    };
+    let start = week_minute(day, start);
+    let len = (week_minute(end_day, end) + MINUTES_PER_WEEK - start) % MINUTES_PER_WEEK;
+    Ok(SpecialWindow { start, len })
+}
+
+fn week_minute(weekday: Weekday, time: NaiveTime) -> u32 {
+    weekday.num_days_from_monday() * MINUTES_PER_DAY + time.hour() * 60 + time.minute()
+}
+
+fn week_minute_weekday(minute: u32) -> Weekday {
+    Weekday::try_from((minute / MINUTES_PER_DAY) as u8).unwrap_or(Weekday::Mon)
+}
+
+fn week_minute_time(minute: u32) -> NaiveTime {
+    let minute = minute % MINUTES_PER_DAY;
+    NaiveTime::from_hms_opt(minute / 60, minute % 60, 0).unwrap_or(NaiveTime::MIN)
+}
+
+fn week_minute_label(minute: u32) -> String {
+    format!(
+        "{} {}",
+        weekday_name(week_minute_weekday(minute)),
+        week_minute_time(minute).format("%H:%M")
+    )
+}
+
+fn parse_time(value: &str) -> Result<NaiveTime, SkipReason> {
+    NaiveTime::parse_from_str(value, "%H:%M").map_err(|_| SkipReason::UnparsableTime {
+        value: value.to_string(),
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Segment {
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+    pub url: String,
+    pub special_id: Option<String>,
+    pub rule: MatchedRule,
+}
+
+/// Everything `resolve` will return between `from` and `until`, merged into segments.
+pub fn timeline(
+    config: Option<&Config>,
+    temp: Option<&TempWallpaper>,
+    from: DateTime<Local>,
+    until: DateTime<Local>,
+) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut at = from;
+    while at < until {
+        let next = next_transition(config, temp, at).unwrap_or(until).min(until);
+        if let Some((target, rule)) = resolve(config, temp, at) {
+            let (url, special_id) = match config.and_then(|c| c.special_urls.get(&target)) {
+                Some(url) => (url.clone(), Some(target)),
+                None => (target, None),
+            };
+            match segments.last_mut() {
+                Some(last) if last.end == at && last.rule == rule && last.url == url => last.end = next,
+                _ => segments.push(Segment {
+                    start: at,
+                    end: next,
+                    url,
+                    special_id,
+                    rule,
+                }),
+            }
+        }
+        if next <= at {
+            break;
+        }
+        at = next;
+    }
+    segments
+}
+
+/// The next instant after `now` at which `resolve` can return something different.
+pub fn next_transition(
+    config: Option<&Config>,
+    temp: Option<&TempWallpaper>,
+    now: DateTime<Local>,
+) -> Option<DateTime<Local>> {
+    if let Some(temp) = temp
+        && now < temp.expiry
+    {
+        return Some(temp.expiry);
+    }
+
+    let config = config?;
+    let zone = Zone::of_config(config);
+    // Dates and weekdays only change at midnight, in every zone a rule is read in.
+    let mut zones = vec![zone];
+    let own_zones = config.wallpapers.specials.iter().map(|period| period.timezone.as_deref());
+    for name in own_zones.chain(config.rules.iter().map(|rule| rule.timezone.as_deref())) {
+        let rule_zone = Zone::of_rule(config, name);
+        if !zones.contains(&rule_zone) {
+            zones.push(rule_zone);
+        }
+    }
+    let mut next = zones
+        .iter()
+        .filter_map(|zone| zone.instant(zone.wall_time(now).date().succ_opt()?.and_time(NaiveTime::MIN)))
+        .min();
+    for period in &config.wallpapers.specials {
+        let zone = Zone::of_rule(config, period.timezone.as_deref());
+        if is_dated(period) {
+            if let Ok(runs_on) = dated_days(period, config)
+                && let Some(at) = dated_boundary(&period.start, &period.end, config, zone, now, runs_on)
+                && next.is_none_or(|n| at < n)
+            {
+                next = Some(at);
+            }
+            continue;
+        }
+        let Ok(window) = special_window(period) else {
+            continue;
+        };
+        let today = zone.wall_time(now).date();
+        for minute in [window.start, window.end()] {
+            let day = week_minute_weekday(minute);
+            let time = week_minute_time(minute);
+            let boundary = (0..=7)
+                .filter_map(|offset| today.checked_add_days(Days::new(offset)))
+                .filter(|date| holidays::effective_weekday(config, *date) == day)
+                .filter_map(|date| zone.instant(date.and_time(time)))
+                .find(|at| *at > now);
+            if let Some(at) = boundary
+                && next.is_none_or(|n| at < n)
+            {
+                next = Some(at);
+            }
+        }
+    }
+    for rule in &config.rules {
+        let zone = Zone::of_rule(config, rule.timezone.as_deref());
+        if let Ok(Some((start, end))) = rule_times(rule)
+            && let Some(at) = dated_boundary(start, end, config, zone, now, |_| true)
+            && next.is_none_or(|n| at < n)
+        {
+            next = Some(at);
+        }
+    }
+    if let Some(limit) = next
+        && let Some(at) = calendars::next_boundary(config, zone.wall_time(now), zone.wall_time(limit))
+        && let Some(at) = zone.instant(at)
+        && at > now
+        && at < limit
+    {
+        next = Some(at);
+    }
+    next
+}
+
+/// The first start or end after `now` of an occurrence starting yesterday, today or
+/// tomorrow; `next_transition` never looks past midnight anyway.
+fn dated_boundary(
+    start: &str,
+    end: &str,
+    config: &Config,
+    zone: Zone,
+    now: DateTime<Local>,
+    runs_on: impl Fn(NaiveDate) -> bool,
+) -> Option<DateTime<Local>> {
+    let today = zone.wall_time(now).date();
+    [today.pred_opt(), Some(today), today.succ_opt()]
+        .into_iter()
+        .flatten()
+        .filter(|date| runs_on(*date))
+        .filter_map(|date| day_window(start, end, config, zone, date).ok().flatten())
+        .flat_map(|(from, until)| [from, until])
+        .filter_map(|at| zone.instant(at))
+        .filter(|at| *at > now)
+        .min()
+}
+
+/// Accepts `YYYY-MM-DD HH:MM`, `YYYY-MM-DDTHH:MM` or a bare `YYYY-MM-DD` (midnight).
+/// `YYYY-MM-DD HH:MM`, `YYYY-MM-DDTHH:MM` or `YYYY-MM-DD` as wall time in `zone`.
+pub fn parse_datetime(value: &str, zone: Zone) -> Result<DateTime<Local>, String> {
+    let naive = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M"))
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y-%m-%d").map(|d| d.and_time(NaiveTime::MIN)))
+        .map_err(|_| format!("Invalid date '{}', expected YYYY-MM-DD HH:MM", value))?;
+    zone.instant(naive)
+        .ok_or_else(|| format!("{} does not exist in the schedule's timezone", value))
+}
+
+pub fn start_of_day(date: NaiveDate, zone: Zone) -> Option<DateTime<Local>> {
+    zone.instant(date.and_time(NaiveTime::MIN))
+}
+
+pub fn weekday_name(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "monday",
+        Weekday::Tue => "tuesday",
+        Weekday::Wed => "wednesday",
+        Weekday::Thu => "thursday",
+        Weekday::Fri => "friday",
+        Weekday::Sat => "saturday",
+        Weekday::Sun => "sunday",
+    }
+}
+
+pub fn weekday_from_str(s: &str) -> Option<Weekday> {
+    match s.to_lowercase().as_str() {
+        "monday" => Some(Weekday::Mon),
+        "tuesday" => Some(Weekday::Tue),
+        "wednesday" => Some(Weekday::Wed),
+        "thursday" => Some(Weekday::Thu),
+        "friday" => Some(Weekday::Fri),
+        "saturday" => Some(Weekday::Sat),
+        "sunday" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_config;
+    use std::collections::HashMap;
+
+    fn config(extra: &str) -> Config {
+        let text = format!(
+            r#"{{"version": 1, "timezone": "Asia/Shanghai", "wallpapers": {{"days": {{"default": "day.jpg"}}{}}}}}"#,
+            extra
+        );
+        parse_config(&text).unwrap().0
+    }
+
+    fn shanghai(value: &str) -> DateTime<Local> {
+        let naive = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap();
+        Zone::Named(chrono_tz::Asia::Shanghai).instant(naive).unwrap()
+    }
+
+    fn url_at(config: &Config, value: &str) -> String {
+        resolve(Some(config), None, shanghai(value)).unwrap().0
+    }
+
+    #[test]
+    fn special_crosses_midnight() {
+        // 2026-10-16 is a Friday.
+        let config = config(r#", "specials": [{"day": "friday", "start": "22:00", "end": "02:00", "url": "night.jpg"}]"#);
+        assert_eq!(url_at(&config, "2026-10-16 21:59"), "day.jpg");
+        assert_eq!(url_at(&config, "2026-10-16 23:00"), "night.jpg");
+        assert_eq!(url_at(&config, "2026-10-17 01:59"), "night.jpg");
+        assert_eq!(url_at(&config, "2026-10-17 02:00"), "day.jpg");
+        assert_eq!(url_at(&config, "2026-10-18 01:00"), "day.jpg");
+        let next = next_transition(Some(&config), None, shanghai("2026-10-17 00:30"));
+        assert_eq!(next, Some(shanghai("2026-10-17 02:00")));
+    }
+
+    #[test]
+    fn special_wraps_past_sunday() {
+        let config = config(r#", "specials": [{"day": "sunday", "start": "23:00", "end": "01:00", "url": "late.jpg"}]"#);
+        assert_eq!(url_at(&config, "2026-10-18 23:30"), "late.jpg");
+        assert_eq!(url_at(&config, "2026-10-19 00:30"), "late.jpg");
+        assert_eq!(url_at(&config, "2026-10-19 01:00"), "day.jpg");
+    }
+
+    #[test]
+    fn special_spans_a_weekend_with_end_day() {
+        let config = config(
+            r#", "specials": [{"day": "friday", "start": "18:00", "end": "08:00", "end_day": "monday", "url": "weekend.jpg"}]"#,
+        );
+        assert_eq!(url_at(&config, "2026-10-16 17:59"), "day.jpg");
+        assert_eq!(url_at(&config, "2026-10-18 12:00"), "weekend.jpg");
+        assert_eq!(url_at(&config, "2026-10-19 07:59"), "weekend.jpg");
+        assert_eq!(url_at(&config, "2026-10-19 08:00"), "day.jpg");
+    }
+
+    #[test]
+    fn calendar_events_use_the_config_timezone() {
+        let mut config = config("");
+        let source: calendars::CalendarSource = serde_json::from_str(r#"{"source": "class.ics"}"#).unwrap();
+        let text = "BEGIN:VCALENDAR\nBEGIN:VEVENT\nSUMMARY:Lunch\nDTSTART:20261020T120000\nDTEND:20261020T130000\nURL:https://example.com/lunch.jpg\nEND:VEVENT\nEND:VCALENDAR\n";
+        let events = calendars::parse_calendar(text, &source, &HashMap::new(), Zone::of_config(&config)).unwrap();
+        config.calendars = vec![calendars::CalendarSource { events, ..source }];
+        assert_eq!(url_at(&config, "2026-10-20 11:59"), "day.jpg");
+        assert_eq!(url_at(&config, "2026-10-20 12:30"), "https://example.com/lunch.jpg");
+        let next = next_transition(Some(&config), None, shanghai("2026-10-20 12:30"));
+        assert_eq!(next, Some(shanghai("2026-10-20 13:00")));
+    }
+
+    #[test]
+    fn dated_rule_ends_at_midnight_in_its_own_timezone() {
+        let mut config = config("");
+        config.timezone = None;
+        config.rules = serde_json::from_str(
+            r#"[{"timezone": "Asia/Shanghai", "dates": ["2026-12-24"], "priority": 500, "url": "xmas.jpg"}]"#,
+        )
+        .unwrap();
+        assert_eq!(url_at(&config, "2026-12-24 12:00"), "xmas.jpg");
+        let next = next_transition(Some(&config), None, shanghai("2026-12-24 12:00")).unwrap();
+        assert!(next <= shanghai("2026-12-25 00:00"), "next transition {}", next);
+    }
+}
@@ -0,0 +1,101 @@
+use crate::calendars;
+use crate::clock::Clock;
+use crate::holidays;
+use crate::overrides::{self, ConfigOverride};
+use crate::schedule::{self, parse_datetime, start_of_day};
+use crate::timezone::Zone;
+use crate::validate::{parse_and_validate, validate_config};
+use chrono::{DateTime, Duration, Local};
+
+pub struct SimulationOptions {
+    pub config_path: String,
+    pub override_path: Option<String>,
+    pub from: DateTime<Local>,
+    pub days: u32,
+}
+
+/// Parses `simulate <config.json> [--override override.json] [--from "YYYY-MM-DD HH:MM"] [--days N]`.
+/// Without `--from`, the simulation starts at the beginning of today by `clock`.
+pub fn parse_args(args: &[String], clock: &dyn Clock) -> Result<SimulationOptions, String> {
+    let mut config_path = None;
+    let mut override_path = None;
+    let mut from = None;
+    let mut days = 7;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--override" => {
+                override_path = Some(iter.next().ok_or("--override needs a value")?.clone());
+            }
+            "--from" => {
+                let value = iter.next().ok_or("--from needs a value")?;
+                from = Some(parse_datetime(value, Zone::Local)?);
+            }
+            "--days" => {
+                let value = iter.next().ok_or("--days needs a value")?;
+                days = value.parse().map_err(|_| format!("Invalid --days value '{}'", value))?;
+            }
+            _ if config_path.is_none() => config_path = Some(arg.clone()),
+            _ => return Err(format!("Unexpected argument '{}'", arg)),
+        }
+    }
+
+    let from = match from {
+        Some(from) => from,
+        None => start_of_day(clock.now().date_naive(), Zone::Local).ok_or("Cannot determine the start of today")?,
+    };
+    Ok(SimulationOptions {
+        config_path: config_path.ok_or("Usage: daily simulate <config.json> [--override override.json] [--from \"YYYY-MM-DD HH:MM\"] [--days N]")?,
+        override_path,
+        from,
+        days,
+    })
+}
+
+pub fn run(options: &SimulationOptions) -> Result<(), String> {
+    let config_text = std::fs::read_to_string(&options.config_path)
+        .map_err(|e| format!("Failed to read {}: {}", options.config_path, e))?;
+    let (config, diagnostics) = parse_and_validate(&config_text, options.from);
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+    let config = config.ok_or_else(|| format!("Failed to parse {}", options.config_path))?;
+    let layer = match &options.override_path {
+        Some(path) => Some(read_override(path)?),
+        None => None,
+    };
+    let (mut merged, mut provenance) = overrides::merge(&config, layer.as_ref());
+    let config_dir = std::path::Path::new(&options.config_path).parent().unwrap_or(std::path::Path::new("."));
+    holidays::load(&mut merged, config_dir)?;
+    for e in calendars::load_files(&mut merged, config_dir) {
+        eprintln!("{}", e);
+    }
+    overrides::block_events(&mut merged, &config, layer.as_ref(), &mut provenance);
+    let config = merged;
+    if layer.is_some() {
+        for diagnostic in validate_config(&config, options.from) {
+            eprintln!("merged: {}", diagnostic);
+        }
+        for entry in &provenance.blocked {
+            eprintln!("blocked: {}: {}", entry.path, entry.value);
+        }
+    }
+
+    println!(
+        "Simulating {} from {} for {} days",
+        options.config_path,
+        options.from.format("%Y-%m-%d %H:%M"),
+        options.days
+    );
+    let until = options.from + Duration::days(options.days as i64);
+    for segment in schedule::timeline(Some(&config), None, options.from, until) {
+        let target = segment.special_id.as_deref().unwrap_or(&segment.url);
+        println!("{}  {:<32} {}", segment.start.format("%Y-%m-%d %a %H:%M"), segment.rule.to_string(), target);
+    }
+    Ok(())
+}
+
+fn read_override(path: &str) -> Result<ConfigOverride, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    overrides::parse_override(&text).map_err(|e| format!("Failed to parse {}: {}", path, e))
+}
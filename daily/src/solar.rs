@@ -0,0 +1,226 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::fmt;
+
+/// Where the sun is observed from, for `sunrise`/`sunset` times.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub struct Location {
+    /// Degrees, north positive.
+    pub latitude: f64,
+    /// Degrees, east positive.
+    pub longitude: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SolarEvent {
+    /// Civil dawn, when the sun is 6° below the horizon.
+    Dawn,
+    Sunrise,
+    Noon,
+    Sunset,
+    Dusk,
+}
+
+impl fmt::Display for SolarEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SolarEvent::Dawn => "dawn",
+            SolarEvent::Sunrise => "sunrise",
+            SolarEvent::Noon => "noon",
+            SolarEvent::Sunset => "sunset",
+            SolarEvent::Dusk => "dusk",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A `start` or `end` value: `HH:MM`, or a solar event with an optional offset like
+/// `sunset-30m` or `sunrise+1h15m`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeSpec {
+    Clock(NaiveTime),
+    Solar { event: SolarEvent, offset: Duration },
+}
+
+impl TimeSpec {
+    pub fn parse(value: &str) -> Result<TimeSpec, String> {
+        let value = value.trim();
+        if let Ok(time) = NaiveTime::parse_from_str(value, "%H:%M") {
+            return Ok(TimeSpec::Clock(time));
+        }
+        let lower = value.to_lowercase();
+        let split = lower.find(['+', '-']).unwrap_or(lower.len());
+        let (name, offset) = lower.split_at(split);
+        let event = match name.trim() {
+            "dawn" => SolarEvent::Dawn,
+            "sunrise" => SolarEvent::Sunrise,
+            "noon" => SolarEvent::Noon,
+            "sunset" => SolarEvent::Sunset,
+            "dusk" => SolarEvent::Dusk,
+            _ => {
+                return Err(format!(
+                    "'{}' is neither HH:MM nor dawn, sunrise, noon, sunset or dusk with an optional offset like -30m",
+                    value
+                ))
+            }
+        };
+        let offset = match offset.chars().next() {
+            None => Duration::zero(),
+            Some(sign) => {
+                let amount = parse_offset(offset[1..].trim()).ok_or_else(|| format!("invalid offset in '{}'", value))?;
+                if sign == '-' { -amount } else { amount }
+            }
+        };
+        Ok(TimeSpec::Solar { event, offset })
+    }
+
+    pub fn is_solar(&self) -> bool {
+        matches!(self, TimeSpec::Solar { .. })
+    }
+}
+
+/// `1h`, `30m` or `1h30m`.
+fn parse_offset(value: &str) -> Option<Duration> {
+    if value.is_empty() {
+        return None;
+    }
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    for c in value.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'h' | 'm' => {
+                let n: i64 = number.parse().ok()?;
+                total += if c == 'h' { Duration::hours(n) } else { Duration::minutes(n) };
+                number.clear();
+            }
+            _ => return None,
+        }
+    }
+    number.is_empty().then_some(total)
+}
+
+/// When `event` happens on `date` at `location`, or `None` when the sun never gets that
+/// high or low (polar day and night). Uses the sunrise equation, good to about a minute.
+pub fn event_time(event: SolarEvent, date: NaiveDate, location: Location) -> Option<DateTime<Utc>> {
+    const J2000: f64 = 2_451_545.0;
+    const UNIX_EPOCH_JULIAN: f64 = 2_440_587.5;
+    let rad = |deg: f64| deg * PI / 180.0;
+
+    // Julian date of noon UTC on `date`.
+    let days_since_epoch = date.num_days_from_ce() as f64 - 719_163.0;
+    let noon = UNIX_EPOCH_JULIAN + days_since_epoch + 0.5;
+    let mean_solar_time = (noon - J2000 + 0.0008).round() - location.longitude / 360.0;
+    let mean_anomaly = (357.5291 + 0.985_600_28 * mean_solar_time).rem_euclid(360.0);
+    let center = 1.9148 * rad(mean_anomaly).sin() + 0.0200 * rad(2.0 * mean_anomaly).sin() + 0.0003 * rad(3.0 * mean_anomaly).sin();
+    let ecliptic_longitude = (mean_anomaly + center + 180.0 + 102.9372).rem_euclid(360.0);
+    let transit = J2000 + mean_solar_time + 0.0053 * rad(mean_anomaly).sin() - 0.0069 * rad(2.0 * ecliptic_longitude).sin();
+    let declination = (rad(ecliptic_longitude).sin() * rad(23.4397).sin()).asin();
+
+    let altitude = match event {
+        SolarEvent::Noon => return julian_to_utc(transit, UNIX_EPOCH_JULIAN),
+        SolarEvent::Sunrise | SolarEvent::Sunset => -0.833,
+        SolarEvent::Dawn | SolarEvent::Dusk => -6.0,
+    };
+    let latitude = rad(location.latitude);
+    let cos_hour_angle =
+        (rad(altitude).sin() - latitude.sin() * declination.sin()) / (latitude.cos() * declination.cos());
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return None;
+    }
+    let hour_angle = cos_hour_angle.acos() * 180.0 / PI;
+    let julian = match event {
+        SolarEvent::Dawn | SolarEvent::Sunrise => transit - hour_angle / 360.0,
+        _ => transit + hour_angle / 360.0,
+    };
+    julian_to_utc(julian, UNIX_EPOCH_JULIAN)
+}
+
+fn julian_to_utc(julian: f64, unix_epoch_julian: f64) -> Option<DateTime<Utc>> {
+    let seconds = ((julian - unix_epoch_julian) * 86_400.0).round() as i64;
+    DateTime::from_timestamp(seconds, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHANGHAI: Location = Location {
+        latitude: 31.23,
+        longitude: 121.47,
+    };
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    /// Published times are rounded to the minute; allow for that and the equation's error.
+    fn assert_near(event: SolarEvent, on: &str, location: Location, expected: &str) {
+        let actual = event_time(event, date(on), location).unwrap();
+        let expected = DateTime::parse_from_rfc3339(expected).unwrap().with_timezone(&Utc);
+        assert!(
+            (actual - expected).num_seconds().abs() <= 120,
+            "{} on {}: got {}, expected {}",
+            event,
+            on,
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn shanghai_solstices() {
+        assert_near(SolarEvent::Sunrise, "2026-06-21", SHANGHAI, "2026-06-21T04:50:00+08:00");
+        assert_near(SolarEvent::Sunset, "2026-06-21", SHANGHAI, "2026-06-21T19:01:00+08:00");
+        assert_near(SolarEvent::Sunrise, "2026-12-21", SHANGHAI, "2026-12-21T06:48:00+08:00");
+        assert_near(SolarEvent::Sunset, "2026-12-21", SHANGHAI, "2026-12-21T16:55:00+08:00");
+    }
+
+    #[test]
+    fn events_in_order() {
+        let on = date("2026-03-20");
+        let london = Location {
+            latitude: 51.51,
+            longitude: -0.13,
+        };
+        assert_near(SolarEvent::Sunrise, "2026-03-20", london, "2026-03-20T06:03:00Z");
+        let times: Vec<_> = [SolarEvent::Dawn, SolarEvent::Sunrise, SolarEvent::Noon, SolarEvent::Sunset, SolarEvent::Dusk]
+            .iter()
+            .map(|event| event_time(*event, on, london).unwrap())
+            .collect();
+        assert!(times.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn polar_day_and_night() {
+        let tromso = Location {
+            latitude: 69.65,
+            longitude: 18.96,
+        };
+        assert_eq!(event_time(SolarEvent::Sunrise, date("2026-12-21"), tromso), None);
+        assert_eq!(event_time(SolarEvent::Sunset, date("2026-06-21"), tromso), None);
+        assert!(event_time(SolarEvent::Noon, date("2026-12-21"), tromso).is_some());
+    }
+
+    #[test]
+    fn time_specs() {
+        assert_eq!(
+            TimeSpec::parse("sunset-30m"),
+            Ok(TimeSpec::Solar {
+                event: SolarEvent::Sunset,
+                offset: Duration::minutes(-30)
+            })
+        );
+        assert_eq!(
+            TimeSpec::parse("Sunrise + 1h15m"),
+            Ok(TimeSpec::Solar {
+                event: SolarEvent::Sunrise,
+                offset: Duration::minutes(75)
+            })
+        );
+        assert_eq!(TimeSpec::parse("07:30"), Ok(TimeSpec::Clock(NaiveTime::from_hms_opt(7, 30, 0).unwrap())));
+        assert!(TimeSpec::parse("sunset-30").is_err());
+        assert!(TimeSpec::parse("moonrise").is_err());
+    }
+}
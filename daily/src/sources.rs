@@ -0,0 +1,179 @@
+use crate::config::Config;
+use crate::state::AppState;
+use log::{debug, error, info, warn};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::mpsc;
+use tokio::time::sleep;
+
+const IMAGE_EXTENSIONS: [&str; 6] = ["jpg", "jpeg", "png", "bmp", "gif", "webp"];
+// Copying from a USB stick fires events for as long as the copy runs; wait until a
+// directory has been quiet this long so a half-written image is never shown.
+const SETTLE_DELAY: Duration = Duration::from_secs(2);
+
+/// The path a `file://` URL points at; `None` for every other target.
+pub fn file_path(target: &str) -> Option<PathBuf> {
+    url::Url::parse(target)
+        .ok()
+        .filter(|url| url.scheme() == "file")?
+        .to_file_path()
+        .ok()
+}
+
+/// Whether a target is shown as a web page: an id in `special_urls`, or a `.html` URL.
+pub fn is_web_page(config: Option<&Config>, target: &str) -> bool {
+    target.ends_with(".html") || config.is_some_and(|config| config.special_urls.contains_key(target))
+}
+
+pub fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|s| s.to_str())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// The images directly in `dir` as `file://` URLs, sorted by name.
+pub fn images(dir: &Path) -> Vec<String> {
+    let mut images: Vec<PathBuf> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.is_file() && is_image(path))
+            .collect(),
+        Err(e) => {
+            warn!("Failed to list {}: {}", dir.display(), e);
+            Vec::new()
+        }
+    };
+    images.sort();
+    images.iter().filter_map(|path| file_url(path)).collect()
+}
+
+/// A `file://` URL naming a directory shows the newest image in it, so a freshly
+/// copied picture goes up at once. Every other target is returned as is, as is an
+/// empty directory.
+pub fn resolve_directory(target: &str) -> String {
+    let Some(dir) = file_path(target).filter(|path| path.is_dir()) else {
+        return target.to_string();
+    };
+    let modified = |url: &String| {
+        file_path(url)
+            .and_then(|path| path.metadata().ok()?.modified().ok())
+            .unwrap_or(SystemTime::UNIX_EPOCH)
+    };
+    images(&dir)
+        .into_iter()
+        .max_by_key(modified)
+        .unwrap_or_else(|| target.to_string())
+}
+
+fn file_url(path: &Path) -> Option<String> {
+    url::Url::from_file_path(path).ok().map(|url| url.to_string())
+}
+
+/// Watches the directories the current wallpaper comes from and wakes the wallpaper
+/// loop when images in them change.
+pub struct DirectoryWatcher {
+    watcher: Option<RecommendedWatcher>,
+    watched: Vec<PathBuf>,
+}
+
+impl DirectoryWatcher {
+    pub fn new(state: Arc<AppState>) -> DirectoryWatcher {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+            tx.send(res).ok();
+        });
+        let watcher = match watcher {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                error!("Failed to create directory watcher: {}", e);
+                None
+            }
+        };
+        tokio::spawn(async move {
+            while let Some(res) = rx.recv().await {
+                if !is_image_change(res) {
+                    continue;
+                }
+                loop {
+                    sleep(SETTLE_DELAY).await;
+                    let mut more = false;
+                    while let Ok(res) = rx.try_recv() {
+                        more |= is_image_change(res);
+                    }
+                    if !more {
+                        break;
+                    }
+                }
+                info!("Images changed in a wallpaper directory, re-checking.");
+                state.wallpaper_notify.notify_one();
+            }
+        });
+        DirectoryWatcher {
+            watcher,
+            watched: Vec::new(),
+        }
+    }
+
+    /// Watches exactly `dirs` from now on.
+    pub fn watch(&mut self, mut dirs: Vec<PathBuf>) {
+        dirs.sort();
+        dirs.dedup();
+        let Some(watcher) = self.watcher.as_mut() else {
+            return;
+        };
+        for dir in self.watched.iter().filter(|dir| !dirs.contains(dir)) {
+            watcher.unwatch(dir).ok();
+            debug!("Stopped watching {}", dir.display());
+        }
+        // A directory that could not be watched (say, the stick is not in yet) is
+        // tried again next time.
+        self.watched.retain(|dir| dirs.contains(dir));
+        for dir in dirs {
+            if self.watched.contains(&dir) {
+                continue;
+            }
+            match watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    info!("Watching {} for new wallpapers", dir.display());
+                    self.watched.push(dir);
+                }
+                Err(e) => warn!("Failed to watch {}: {}", dir.display(), e),
+            }
+        }
+    }
+}
+
+fn is_image_change(res: notify::Result<Event>) -> bool {
+    match res {
+        Ok(event) => {
+            !matches!(event.kind, EventKind::Access(_)) && event.paths.iter().any(|path| is_image(path))
+        }
+        Err(e) => {
+            warn!("Directory watcher error: {}", e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_config;
+
+    #[test]
+    fn web_pages_are_special_urls_ids_or_html() {
+        let (config, _) = parse_config(
+            r#"{"version": 1, "wallpapers": {}, "special_urls": {"lobby": "https://example.com/lobby.html"}}"#,
+        )
+        .unwrap();
+        // Any id in special_urls is a page, whatever it is called...
+        assert!(is_web_page(Some(&config), "lobby"));
+        assert!(is_web_page(Some(&config), "https://example.com/clock.html"));
+        // ...and an id that only looks like one is not.
+        assert!(!is_web_page(Some(&config), "special1"));
+        assert!(!is_web_page(Some(&config), "https://example.com/a.jpg"));
+        assert!(!is_web_page(None, "lobby"));
+    }
+}
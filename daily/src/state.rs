@@ -1,6 +1,11 @@
+use crate::backend::WallpaperBackend;
+use crate::clock::Clock;
 use crate::config::Config;
+use crate::overrides::Provenance;
+use crate::playlist::Rotations;
 use chrono::{DateTime, Local};
 use std::path::PathBuf;
+use std::sync::Arc;
 use tokio::sync::{Mutex, Notify};
 
 #[derive(Debug, Clone)]
@@ -10,10 +15,19 @@ pub struct TempWallpaper {
 }
 
 pub struct AppState {
+    /// The effective config: `remote_config` with `override.json` merged on top.
     pub config: Mutex<Option<Config>>,
+    pub remote_config: Mutex<Option<Config>>,
+    pub provenance: Mutex<Provenance>,
     pub temp_wallpaper: Mutex<Option<TempWallpaper>>,
+    pub rotations: Mutex<Rotations>,
     pub current_wallpaper_url: Mutex<String>,
+    /// The sha256 of the last config.json we downloaded and wrote, so the config
+    /// watcher can tell our own writes from local edits.
+    pub written_config: Mutex<Option<String>>,
     pub app_data_dir: PathBuf,
     pub wallpaper_notify: Notify,
     pub web_wallpaper_pid: Mutex<Option<u32>>,
+    pub backend: Box<dyn WallpaperBackend>,
+    pub clock: Arc<dyn Clock>,
 }
\ No newline at end of file
@@ -0,0 +1,272 @@
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Term {
+    pub name: String,
+    /// Inclusive `YYYY-MM-DD` dates.
+    pub start: String,
+    pub end: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub breaks: Vec<TermBreak>,
+    #[serde(default)]
+    pub week_numbering: WeekNumbering,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TermBreak {
+    pub name: String,
+    pub start: String,
+    pub end: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WeekNumbering {
+    /// Monday-to-Sunday weeks, week 1 contains the term start.
+    #[default]
+    Calendar,
+    /// Seven-day blocks counted from the term start.
+    Elapsed,
+    /// Like `calendar`, but weeks spent entirely in a break are not counted.
+    SkipBreaks,
+}
+
+/// Where a date falls in the term calendar.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TermPosition {
+    InTerm { term: String, week: i64 },
+    InBreak { term: String, name: String, week: Option<i64> },
+    BetweenTerms,
+}
+
+impl fmt::Display for TermPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TermPosition::InTerm { term, week } => write!(f, "{}, week {}", term, week),
+            TermPosition::InBreak { term, name, .. } => write!(f, "{} ({})", name, term),
+            TermPosition::BetweenTerms => write!(f, "between terms"),
+        }
+    }
+}
+
+impl TermPosition {
+    pub fn week(&self) -> Option<i64> {
+        match self {
+            TermPosition::InTerm { week, .. } => Some(*week),
+            TermPosition::InBreak { week, .. } => *week,
+            TermPosition::BetweenTerms => None,
+        }
+    }
+}
+
+pub fn parse_day(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .filter(|_| value.len() == 10)
+}
+
+/// `None` when no terms are configured; the first term containing `date` wins.
+pub fn locate(terms: &[Term], date: NaiveDate) -> Option<TermPosition> {
+    if terms.is_empty() {
+        return None;
+    }
+    for term in terms {
+        let (Some(start), Some(end)) = (parse_day(&term.start), parse_day(&term.end)) else {
+            continue;
+        };
+        if date < start || date > end {
+            continue;
+        }
+        let week = term_week(term, start, date);
+        if let Some(brk) = term.breaks.iter().find(|brk| break_contains(brk, date)) {
+            return Some(TermPosition::InBreak {
+                term: term.name.clone(),
+                name: brk.name.clone(),
+                week,
+            });
+        }
+        return Some(TermPosition::InTerm {
+            term: term.name.clone(),
+            week: week.unwrap_or(0),
+        });
+    }
+    Some(TermPosition::BetweenTerms)
+}
+
+/// Resolves `term:<name>` or `break:<name>` to its inclusive dates.
+pub fn named_span(terms: &[Term], key: &str) -> Option<Result<(NaiveDate, NaiveDate), String>> {
+    let (kind, name) = key.split_once(':')?;
+    let span = match kind {
+        "term" => terms
+            .iter()
+            .find(|term| term.name == name)
+            .map(|term| (&term.start, &term.end)),
+        "break" => terms
+            .iter()
+            .flat_map(|term| &term.breaks)
+            .find(|brk| brk.name == name)
+            .map(|brk| (&brk.start, &brk.end)),
+        _ => return None,
+    };
+    let result = match span {
+        Some((start, end)) => match (parse_day(start), parse_day(end)) {
+            (Some(start), Some(end)) => Ok((start, end)),
+            _ => Err(format!("{} '{}' has invalid dates", kind, name)),
+        },
+        None => Err(format!("no {} named '{}' in terms", kind, name)),
+    };
+    Some(result)
+}
+
+fn break_contains(brk: &TermBreak, date: NaiveDate) -> bool {
+    matches!((parse_day(&brk.start), parse_day(&brk.end)), (Some(start), Some(end)) if start <= date && date <= end)
+}
+
+fn monday(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(date.weekday().num_days_from_monday() as i64)
+}
+
+/// Week 1 is the Monday-to-Sunday week containing `start`; earlier weeks count down
+/// through 0 and below.
+pub fn calendar_week(start: NaiveDate, date: NaiveDate) -> i64 {
+    (monday(date) - monday(start)).num_days().div_euclid(7) + 1
+}
+
+fn term_week(term: &Term, start: NaiveDate, date: NaiveDate) -> Option<i64> {
+    match term.week_numbering {
+        WeekNumbering::Calendar => Some(calendar_week(start, date)),
+        WeekNumbering::Elapsed => Some((date - start).num_days() / 7 + 1),
+        WeekNumbering::SkipBreaks => {
+            let in_break = |week: NaiveDate| (0..7).all(|d| break_contains_any(term, week + Duration::days(d)));
+            if in_break(monday(date)) {
+                return None;
+            }
+            let mut week = 0;
+            let mut at = monday(start);
+            while at <= date {
+                if !in_break(at) {
+                    week += 1;
+                }
+                at += Duration::days(7);
+            }
+            Some(week)
+        }
+    }
+}
+
+fn break_contains_any(term: &Term, date: NaiveDate) -> bool {
+    term.breaks.iter().any(|brk| break_contains(brk, date))
+}
+
+/// Parses week lists like `1-8,10,12-14`.
+pub fn parse_weeks(value: &str) -> Result<Vec<(i64, i64)>, String> {
+    value
+        .split(',')
+        .map(|part| {
+            let part = part.trim();
+            let (from, to) = part.split_once('-').unwrap_or((part, part));
+            match (from.trim().parse::<i64>(), to.trim().parse::<i64>()) {
+                (Ok(from), Ok(to)) if from <= to => Ok((from, to)),
+                _ => Err(format!("'{}' is not a week number or range like 1-8", part)),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(value: &str) -> NaiveDate {
+        parse_day(value).unwrap()
+    }
+
+    /// An autumn term starting on a Wednesday, with a break covering the whole week
+    /// of 2026-10-05, and a spring term.
+    fn terms(week_numbering: WeekNumbering) -> Vec<Term> {
+        vec![
+            Term {
+                name: "autumn".to_string(),
+                start: "2026-09-02".to_string(),
+                end: "2027-01-15".to_string(),
+                breaks: vec![TermBreak {
+                    name: "national day".to_string(),
+                    start: "2026-10-01".to_string(),
+                    end: "2026-10-11".to_string(),
+                }],
+                week_numbering,
+            },
+            Term {
+                name: "spring".to_string(),
+                start: "2027-02-22".to_string(),
+                end: "2027-06-30".to_string(),
+                breaks: Vec::new(),
+                week_numbering,
+            },
+        ]
+    }
+
+    fn week(terms: &[Term], day: &str) -> Option<i64> {
+        locate(terms, date(day)).and_then(|position| position.week())
+    }
+
+    #[test]
+    fn week_one_starts_mid_week() {
+        let terms = terms(WeekNumbering::Calendar);
+        assert_eq!(week(&terms, "2026-09-02"), Some(1));
+        assert_eq!(week(&terms, "2026-09-06"), Some(1));
+        assert_eq!(week(&terms, "2026-09-07"), Some(2));
+        // Days before the start count down through week 0.
+        assert_eq!(calendar_week(date("2026-09-02"), date("2026-08-30")), 0);
+        assert_eq!(calendar_week(date("2026-09-02"), date("2026-08-23")), -1);
+
+        let elapsed = self::terms(WeekNumbering::Elapsed);
+        assert_eq!(week(&elapsed, "2026-09-08"), Some(1));
+        assert_eq!(week(&elapsed, "2026-09-09"), Some(2));
+    }
+
+    #[test]
+    fn breaks_and_their_weeks() {
+        let terms = terms(WeekNumbering::Calendar);
+        assert_eq!(
+            locate(&terms, date("2026-10-02")),
+            Some(TermPosition::InBreak {
+                term: "autumn".to_string(),
+                name: "national day".to_string(),
+                week: Some(5),
+            })
+        );
+        assert_eq!(week(&terms, "2026-10-12"), Some(7));
+
+        // The week spent wholly in the break is not counted.
+        let skip = self::terms(WeekNumbering::SkipBreaks);
+        assert_eq!(week(&skip, "2026-10-02"), Some(5));
+        assert_eq!(week(&skip, "2026-10-06"), None);
+        assert_eq!(week(&skip, "2026-10-12"), Some(6));
+    }
+
+    #[test]
+    fn dates_outside_every_term() {
+        let terms = terms(WeekNumbering::Calendar);
+        assert_eq!(locate(&terms, date("2026-08-31")), Some(TermPosition::BetweenTerms));
+        assert_eq!(locate(&terms, date("2027-02-01")), Some(TermPosition::BetweenTerms));
+        assert_eq!(
+            locate(&terms, date("2027-02-22")),
+            Some(TermPosition::InTerm {
+                term: "spring".to_string(),
+                week: 1,
+            })
+        );
+        assert_eq!(locate(&[], date("2026-10-12")), None);
+    }
+
+    #[test]
+    fn week_lists() {
+        assert_eq!(parse_weeks("1-8, 10"), Ok(vec![(1, 8), (10, 10)]));
+        assert!(parse_weeks("8-1").is_err());
+        assert!(parse_weeks("odd").is_err());
+    }
+}
@@ -0,0 +1,165 @@
+use crate::config::Config;
+use chrono::{DateTime, Duration, Local, LocalResult, NaiveDateTime, Offset, TimeZone};
+use chrono_tz::Tz;
+use std::fmt;
+
+/// The zone a schedule's wall-clock times are read in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Zone {
+    /// The machine's own timezone.
+    Local,
+    Named(Tz),
+}
+
+impl fmt::Display for Zone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Zone::Local => write!(f, "machine local"),
+            Zone::Named(tz) => write!(f, "{}", tz.name()),
+        }
+    }
+}
+
+impl Zone {
+    /// An IANA name like `Asia/Shanghai`; `None` is the machine's zone.
+    pub fn parse(name: Option<&str>) -> Result<Zone, String> {
+        match name {
+            None => Ok(Zone::Local),
+            Some(name) => name
+                .parse::<Tz>()
+                .map(Zone::Named)
+                .map_err(|_| format!("'{}' is not an IANA timezone name (expected e.g. \"Asia/Shanghai\")", name)),
+        }
+    }
+
+    /// The config's `timezone`; an invalid name (which validation reports) falls back to
+    /// the machine's zone.
+    pub fn of_config(config: &Config) -> Zone {
+        Zone::lenient(config.timezone.as_deref(), Zone::Local)
+    }
+
+    /// A rule's own `timezone`, else the config's.
+    pub fn of_rule(config: &Config, timezone: Option<&str>) -> Zone {
+        Zone::lenient(timezone, Zone::of_config(config))
+    }
+
+    fn lenient(name: Option<&str>, fallback: Zone) -> Zone {
+        match name {
+            Some(name) => Zone::parse(Some(name)).unwrap_or(fallback),
+            None => fallback,
+        }
+    }
+
+    /// The wall-clock time in this zone at `at`.
+    pub fn wall_time(&self, at: DateTime<Local>) -> NaiveDateTime {
+        match self {
+            Zone::Local => at.naive_local(),
+            Zone::Named(tz) => at.with_timezone(tz).naive_local(),
+        }
+    }
+
+    /// `at` in this zone with its UTC offset, e.g. `2026-10-18 14:00:00 +02:00`.
+    pub fn describe(&self, at: DateTime<Local>) -> String {
+        const FORMAT: &str = "%Y-%m-%d %H:%M:%S %:z";
+        match self {
+            Zone::Local => at.format(FORMAT).to_string(),
+            Zone::Named(tz) => at.with_timezone(tz).format(FORMAT).to_string(),
+        }
+    }
+
+    /// The instant a wall-clock time in this zone refers to. See [`resolve`].
+    pub fn instant(&self, naive: NaiveDateTime) -> Option<DateTime<Local>> {
+        match self {
+            Zone::Local => resolve(&Local, naive),
+            Zone::Named(tz) => resolve(tz, naive).map(|at| at.with_timezone(&Local)),
+        }
+    }
+}
+
+/// Maps a wall-clock time to an instant. In a repeated hour (clocks going back) this is
+/// the first occurrence, so a window covering that hour covers both passes. A time in a
+/// DST gap (clocks going forward) maps to the moment of the jump, the first instant whose
+/// wall time is past it: on a 02:00 to 03:00 jump, 02:30 becomes 03:00.
+pub fn resolve<Z: TimeZone>(tz: &Z, naive: NaiveDateTime) -> Option<DateTime<Z>> {
+    match tz.from_local_datetime(&naive) {
+        LocalResult::Single(at) => Some(at),
+        LocalResult::Ambiguous(earliest, _) => Some(earliest),
+        LocalResult::None => {
+            // The offsets either side of the gap bracket the jump; no gap is three hours.
+            let before = tz.from_local_datetime(&(naive - Duration::hours(3))).earliest()?;
+            let after = tz.from_local_datetime(&(naive + Duration::hours(3))).latest()?;
+            let mut low = naive - after.offset().fix();
+            let mut high = naive - before.offset().fix();
+            // Offsets change on whole seconds, so halve in whole seconds too.
+            while high - low > Duration::seconds(1) {
+                let mid = low + Duration::seconds((high - low).num_seconds() / 2);
+                if tz.from_utc_datetime(&mid).naive_local() < naive {
+                    low = mid;
+                } else {
+                    high = mid;
+                }
+            }
+            Some(tz.from_utc_datetime(&high))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_config;
+    use chrono::Utc;
+    use chrono_tz::{America, Australia, Europe};
+
+    fn at(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn utc<Z: TimeZone>(tz: &Z, naive: &str) -> NaiveDateTime {
+        resolve(tz, at(naive)).unwrap().with_timezone(&Utc).naive_utc()
+    }
+
+    #[test]
+    fn gap_times_move_to_the_jump() {
+        // 02:00 becomes 03:00 on both; anything in between is the jump itself.
+        assert_eq!(utc(&America::New_York, "2026-03-08 02:30"), at("2026-03-08 07:00"));
+        assert_eq!(utc(&America::New_York, "2026-03-08 03:00"), at("2026-03-08 07:00"));
+        assert_eq!(utc(&Europe::Berlin, "2026-03-29 02:00"), at("2026-03-29 01:00"));
+        assert_eq!(utc(&Europe::Berlin, "2026-03-29 02:59"), at("2026-03-29 01:00"));
+        // Lord Howe only moves half an hour.
+        assert_eq!(utc(&Australia::Lord_Howe, "2026-10-04 02:15"), at("2026-10-03 15:30"));
+    }
+
+    #[test]
+    fn repeated_times_take_the_first_pass() {
+        assert_eq!(utc(&America::New_York, "2026-11-01 01:30"), at("2026-11-01 05:30"));
+        assert_eq!(utc(&Europe::Berlin, "2026-10-25 02:30"), at("2026-10-25 00:30"));
+        assert_eq!(utc(&Europe::Berlin, "2026-10-25 03:00"), at("2026-10-25 02:00"));
+    }
+
+    #[test]
+    fn zone_round_trips_wall_time() {
+        let zone = Zone::Named(Europe::Berlin);
+        let instant = zone.instant(at("2026-10-18 14:00")).unwrap();
+        assert_eq!(zone.wall_time(instant), at("2026-10-18 14:00"));
+        assert_eq!(zone.describe(instant), "2026-10-18 14:00:00 +02:00");
+    }
+
+    #[test]
+    fn invalid_names_fall_back() {
+        assert!(Zone::parse(Some("Mars/Olympus")).is_err());
+        assert_eq!(Zone::parse(None), Ok(Zone::Local));
+        assert_eq!(Zone::parse(Some("Asia/Shanghai")), Ok(Zone::Named(chrono_tz::Asia::Shanghai)));
+
+        let config = |timezone: &str| {
+            parse_config(&format!(r#"{{"version": 1, "timezone": "{}", "wallpapers": {{}}}}"#, timezone))
+                .unwrap()
+                .0
+        };
+        assert_eq!(Zone::of_config(&config("Mars/Olympus")), Zone::Local);
+        let berlin = config("Europe/Berlin");
+        assert_eq!(Zone::of_rule(&berlin, Some("Mars/Olympus")), Zone::Named(Europe::Berlin));
+        assert_eq!(Zone::of_rule(&berlin, None), Zone::Named(Europe::Berlin));
+        assert_eq!(Zone::of_rule(&berlin, Some("America/New_York")), Zone::Named(America::New_York));
+    }
+}
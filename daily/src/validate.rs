@@ -0,0 +1,704 @@
+use crate::config::{parse_config, Config, SpecialPeriod};
+use crate::dates::DateKey;
+use crate::generate::{self, Generated};
+use crate::holidays::{self, BUNDLED_CALENDARS};
+use crate::playlist::{Order, PlaylistItem};
+use crate::repeat::Repeat;
+use crate::rules::{Rule, TEMP_PRIORITY};
+use crate::schedule::{is_dated, special_window, weekday_from_str, weekday_name};
+use crate::solar::TimeSpec;
+use crate::sources;
+use crate::terms::{parse_day, parse_weeks};
+use crate::timezone::Zone;
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, Weekday};
+use serde::Serialize;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}: {}", severity, self.path, self.message)
+    }
+}
+
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|d| d.severity == Severity::Error)
+}
+
+/// Parses, migrates and validates a config. Migrations are reported as warnings and a
+/// parse failure as a single error at `$`. `now` decides which year's holidays must be known.
+pub fn parse_and_validate(text: &str, now: DateTime<Local>) -> (Option<Config>, Vec<Diagnostic>) {
+    match parse_config(text) {
+        Ok((config, notes)) => {
+            let mut diagnostics: Vec<Diagnostic> = notes
+                .into_iter()
+                .map(|note| warning(note.path, format!("migrated: {}", note.message)))
+                .collect();
+            diagnostics.extend(validate_config(&config, now));
+            (Some(config), diagnostics)
+        }
+        Err(e) => (None, vec![error("$".to_string(), e)]),
+    }
+}
+
+pub fn validate_config(config: &Config, now: DateTime<Local>) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let days = &config.wallpapers.days;
+    for (name, url) in days.entries() {
+        if let Some(url) = url {
+            check_target(config, &format!("$.wallpapers.days.{}", name), url, &mut diagnostics);
+        }
+    }
+    let uncovered: Vec<&str> = (0..7)
+        .filter_map(|n| Weekday::try_from(n).ok())
+        .filter(|weekday| days.lookup(*weekday).is_none())
+        .map(weekday_name)
+        .collect();
+    if !uncovered.is_empty() {
+        diagnostics.push(warning(
+            "$.wallpapers.days".to_string(),
+            format!("nothing covers {}; add them, a group or default", uncovered.join(", ")),
+        ));
+    }
+
+    let mut date_keys: Vec<&String> = config.wallpapers.dates.keys().collect();
+    date_keys.sort();
+    for key in date_keys {
+        let path = format!("$.wallpapers.dates[\"{}\"]", key);
+        if let Err(e) = DateKey::parse_with_terms(key, &config.terms) {
+            diagnostics.push(error(path.clone(), e));
+        }
+        check_target(config, &path, &config.wallpapers.dates[key], &mut diagnostics);
+    }
+
+    check_timezone("$.timezone", config.timezone.as_deref(), &mut diagnostics);
+    if let Some(location) = config.location {
+        if !(-90.0..=90.0).contains(&location.latitude) {
+            diagnostics.push(error(
+                "$.location.latitude".to_string(),
+                format!("{} is outside -90 to 90", location.latitude),
+            ));
+        }
+        if !(-180.0..=180.0).contains(&location.longitude) {
+            diagnostics.push(error(
+                "$.location.longitude".to_string(),
+                format!("{} is outside -180 to 180", location.longitude),
+            ));
+        }
+    }
+    if let Some(term_start) = &config.wallpapers.term_start {
+        check_date("$.wallpapers.term_start", term_start, &mut diagnostics);
+    }
+    check_terms(config, &mut diagnostics);
+    check_holiday_calendar(config, now, &mut diagnostics);
+    for (i, calendar) in config.calendars.iter().enumerate() {
+        let path = format!("$.calendars[{}]", i);
+        if calendar.source.trim().is_empty() {
+            diagnostics.push(error(format!("{}.source", path), "must not be empty".to_string()));
+        }
+        let mut keys: Vec<&String> = calendar.map.keys().collect();
+        keys.sort();
+        for key in keys {
+            check_target(config, &format!("{}.map[\"{}\"]", path, key), &calendar.map[key], &mut diagnostics);
+        }
+        if let Some(default_url) = &calendar.default_url {
+            check_target(config, &format!("{}.default_url", path), default_url, &mut diagnostics);
+        }
+    }
+
+    let mut periods = Vec::new();
+    for (i, period) in config.wallpapers.specials.iter().enumerate() {
+        let path = format!("$.wallpapers.specials[{}]", i);
+        check_timezone(&format!("{}.timezone", path), period.timezone.as_deref(), &mut diagnostics);
+        if is_dated(period) {
+            check_dated(config, &path, period, &mut diagnostics);
+            continue;
+        }
+        let day = weekday_from_str(&period.day);
+        if day.is_none() {
+            diagnostics.push(error(
+                format!("{}.day", path),
+                format!("'{}' is not a weekday name (expected e.g. \"monday\")", period.day),
+            ));
+        }
+        let start = check_time(&format!("{}.start", path), &period.start, &mut diagnostics);
+        let end = check_time(&format!("{}.end", path), &period.end, &mut diagnostics);
+        let end_day = match &period.end_day {
+            Some(end_day) => weekday_from_str(end_day),
+            None => day,
+        };
+        if end_day.is_none() && let Some(end_day) = &period.end_day {
+            diagnostics.push(error(
+                format!("{}.end_day", path),
+                format!("'{}' is not a weekday name (expected e.g. \"saturday\")", end_day),
+            ));
+        }
+        check_target(config, &format!("{}.url", path), &period.url, &mut diagnostics);
+        check_scope(config, &path, period, &mut diagnostics);
+
+        if day.is_some() && end_day.is_some() && start.is_some() && end.is_some() {
+            match special_window(period) {
+                Ok(window) if window.len > 0 && !(period.end_day.is_none() && start == end) => {
+                    periods.push((i, window, is_scoped(period)))
+                }
+                _ => diagnostics.push(error(
+                    path.clone(),
+                    format!("start and end are both {}, set end_day for a period of whole days", period.start),
+                )),
+            }
+        }
+    }
+
+    // Scoped periods may well never meet, so only unscoped ones are worth a warning.
+    for (n, &(i, window, scoped)) in periods.iter().enumerate() {
+        for &(j, other, other_scoped) in &periods[..n] {
+            if !scoped && !other_scoped && window.overlaps(&other) {
+                diagnostics.push(warning(
+                    format!("$.wallpapers.specials[{}]", i),
+                    format!("overlaps specials[{}]; the earlier entry wins", j),
+                ));
+            }
+        }
+    }
+
+    for (i, rule) in config.rules.iter().enumerate() {
+        check_rule(config, &format!("$.rules[{}]", i), rule, &mut diagnostics);
+    }
+
+    let mut special_ids: Vec<&String> = config.special_urls.keys().collect();
+    special_ids.sort();
+    for id in special_ids {
+        let url = &config.special_urls[id];
+        if !is_url(url) {
+            diagnostics.push(error(
+                format!("$.special_urls.{}", id),
+                format!("'{}' is not a valid http(s) or file URL", url),
+            ));
+        }
+    }
+
+    let mut names: Vec<&String> = config.playlists.keys().collect();
+    names.sort();
+    for name in names {
+        let path = format!("$.playlists.{}", name);
+        let playlist = &config.playlists[name];
+        if config.special_urls.contains_key(name) {
+            diagnostics.push(error(path.clone(), "is also a special_urls id".to_string()));
+        }
+        match &playlist.directory {
+            Some(directory) if sources::file_path(directory).is_none() => diagnostics.push(error(
+                format!("{}.directory", path),
+                format!("'{}' is not a file:// URL", directory),
+            )),
+            Some(_) => {}
+            None if playlist.items.is_empty() => {
+                diagnostics.push(error(format!("{}.items", path), "must not be empty without a directory".to_string()))
+            }
+            None => {}
+        }
+        if playlist.interval_minutes == 0 {
+            diagnostics.push(error(format!("{}.interval_minutes", path), "must be at least 1".to_string()));
+        }
+        for (k, item) in playlist.items.iter().enumerate() {
+            let item_path = format!("{}.items[{}]", path, k);
+            if config.playlists.contains_key(item.url()) {
+                diagnostics.push(error(item_path.clone(), "playlists cannot contain playlists".to_string()));
+            } else {
+                check_target(config, &item_path, item.url(), &mut diagnostics);
+            }
+            if matches!(item, PlaylistItem::Weighted { .. }) && playlist.order != Order::Weighted {
+                diagnostics.push(warning(
+                    format!("{}.weight", item_path),
+                    "only matters with order \"weighted\"".to_string(),
+                ));
+            }
+        }
+        if playlist.order == Order::Weighted
+            && !playlist.items.is_empty()
+            && playlist.items.iter().all(|item| item.weight() == 0)
+        {
+            diagnostics.push(error(format!("{}.items", path), "need a weight above 0".to_string()));
+        }
+    }
+
+    diagnostics
+}
+
+/// Specials checked date by date, see [`is_dated`]. They are left out of the overlap
+/// check, which only knows weekly windows.
+fn check_dated(config: &Config, path: &str, period: &SpecialPeriod, diagnostics: &mut Vec<Diagnostic>) {
+    match &period.repeat {
+        Some(value) => {
+            match Repeat::parse(value, Zone::of_rule(config, period.timezone.as_deref())) {
+                Ok(repeat)
+                    if repeat.needs_anchor() && period.from.is_none() && config.wallpapers.term_start.is_none() =>
+                {
+                    diagnostics.push(warning(
+                        format!("{}.repeat", path),
+                        "counts from 2024-01-01 (a Monday); set from to choose the first occurrence".to_string(),
+                    ))
+                }
+                Ok(_) => {}
+                Err(e) => diagnostics.push(error(format!("{}.repeat", path), e)),
+            }
+            if !period.day.is_empty() {
+                diagnostics.push(warning(format!("{}.day", path), "ignored when repeat is set".to_string()));
+            }
+        }
+        None if weekday_from_str(&period.day).is_none() => diagnostics.push(error(
+            format!("{}.day", path),
+            format!("'{}' is not a weekday name (expected e.g. \"monday\")", period.day),
+        )),
+        None => {}
+    }
+    if period.end_day.is_some() {
+        let reason = if period.repeat.is_some() { "repeat" } else { "sun times" };
+        diagnostics.push(error(
+            format!("{}.end_day", path),
+            format!("cannot be combined with {}; an end at or before start is on the next day", reason),
+        ));
+    }
+    check_time_spec(config, &format!("{}.start", path), &period.start, diagnostics);
+    check_time_spec(config, &format!("{}.end", path), &period.end, diagnostics);
+    check_target(config, &format!("{}.url", path), &period.url, diagnostics);
+    check_scope(config, path, period, diagnostics);
+}
+
+fn check_rule(config: &Config, path: &str, rule: &Rule, diagnostics: &mut Vec<Diagnostic>) {
+    if rule.priority == TEMP_PRIORITY {
+        diagnostics.push(warning(
+            format!("{}.priority", path),
+            "is reserved for temp wallpapers, which still win".to_string(),
+        ));
+    }
+    for (k, day) in rule.weekdays.iter().enumerate() {
+        if weekday_from_str(day).is_none() {
+            diagnostics.push(error(
+                format!("{}.weekdays[{}]", path, k),
+                format!("'{}' is not a weekday name (expected e.g. \"monday\")", day),
+            ));
+        }
+    }
+    for (k, key) in rule.dates.iter().enumerate() {
+        if let Err(e) = DateKey::parse_with_terms(key, &config.terms) {
+            diagnostics.push(error(format!("{}.dates[{}]", path, k), e));
+        }
+    }
+    match (&rule.start, &rule.end) {
+        (Some(start), Some(end)) => {
+            let start_spec = check_time_spec(config, &format!("{}.start", path), start, diagnostics);
+            let end_spec = check_time_spec(config, &format!("{}.end", path), end, diagnostics);
+            if start_spec.is_some() && start_spec == end_spec {
+                diagnostics.push(error(
+                    path.to_string(),
+                    format!("start and end are both {}, leave both out for the whole day", start),
+                ));
+            }
+        }
+        (None, None) => {}
+        (Some(_), None) => diagnostics.push(error(format!("{}.end", path), "is required with start".to_string())),
+        (None, Some(_)) => diagnostics.push(error(format!("{}.start", path), "is required with end".to_string())),
+    }
+    for (k, host) in rule.hostnames.iter().enumerate() {
+        if host.trim().is_empty() {
+            diagnostics.push(error(format!("{}.hostnames[{}]", path, k), "must not be empty".to_string()));
+        }
+    }
+    if let Some(weeks) = &rule.weeks {
+        if let Err(e) = parse_weeks(weeks) {
+            diagnostics.push(error(format!("{}.weeks", path), e));
+        } else if config.wallpapers.term_start.is_none() && config.terms.is_empty() {
+            diagnostics.push(error(
+                format!("{}.weeks", path),
+                "needs $.terms or $.wallpapers.term_start".to_string(),
+            ));
+        }
+    }
+    check_timezone(&format!("{}.timezone", path), rule.timezone.as_deref(), diagnostics);
+    check_target(config, &format!("{}.url", path), &rule.url, diagnostics);
+}
+
+fn check_scope(config: &Config, path: &str, period: &SpecialPeriod, diagnostics: &mut Vec<Diagnostic>) {
+    for (k, key) in period.dates.iter().enumerate() {
+        if let Err(e) = DateKey::parse_with_terms(key, &config.terms) {
+            diagnostics.push(error(format!("{}.dates[{}]", path, k), e));
+        }
+    }
+    let from = period
+        .from
+        .as_ref()
+        .and_then(|from| check_date(&format!("{}.from", path), from, diagnostics));
+    let until = period
+        .until
+        .as_ref()
+        .and_then(|until| check_date(&format!("{}.until", path), until, diagnostics));
+    if let (Some(from), Some(until)) = (from, until)
+        && until < from
+    {
+        diagnostics.push(error(path.to_string(), format!("until {} is before from {}", until, from)));
+    }
+
+    if period.every_n_weeks == Some(0) {
+        diagnostics.push(error(
+            format!("{}.every_n_weeks", path),
+            "must be at least 1".to_string(),
+        ));
+    }
+    if let Some(parity) = &period.week_parity
+        && !matches!(parity.to_lowercase().as_str(), "odd" | "even")
+    {
+        diagnostics.push(error(
+            format!("{}.week_parity", path),
+            format!("'{}' is neither \"odd\" nor \"even\"", parity),
+        ));
+    }
+    if let Some(weeks) = &period.weeks
+        && let Err(e) = parse_weeks(weeks)
+    {
+        diagnostics.push(error(format!("{}.weeks", path), e));
+    }
+    let uses_weeks = period.every_n_weeks.is_some() || period.week_parity.is_some() || period.weeks.is_some();
+    if uses_weeks && config.wallpapers.term_start.is_none() && config.terms.is_empty() {
+        diagnostics.push(error(
+            path.to_string(),
+            "every_n_weeks, week_parity and weeks need $.terms or $.wallpapers.term_start".to_string(),
+        ));
+    }
+    if let Some(during) = &period.during {
+        if !matches!(during.as_str(), "term" | "break" | "always") {
+            diagnostics.push(error(
+                format!("{}.during", path),
+                format!("'{}' is not one of \"term\", \"break\" or \"always\"", during),
+            ));
+        } else if config.terms.is_empty() {
+            diagnostics.push(warning(
+                format!("{}.during", path),
+                "has no effect without $.terms".to_string(),
+            ));
+        }
+    }
+}
+
+fn check_terms(config: &Config, diagnostics: &mut Vec<Diagnostic>) {
+    let mut spans = Vec::new();
+    for (i, term) in config.terms.iter().enumerate() {
+        let path = format!("$.terms[{}]", i);
+        if term.name.is_empty() {
+            diagnostics.push(error(format!("{}.name", path), "must not be empty".to_string()));
+        } else if config.terms[..i].iter().any(|other| other.name == term.name) {
+            diagnostics.push(error(format!("{}.name", path), format!("duplicate term name '{}'", term.name)));
+        }
+        let Some((start, end)) = check_span(&path, &term.start, &term.end, diagnostics) else {
+            continue;
+        };
+        for (j, brk) in term.breaks.iter().enumerate() {
+            let break_path = format!("{}.breaks[{}]", path, j);
+            if let Some((break_start, break_end)) = check_span(&break_path, &brk.start, &brk.end, diagnostics)
+                && (break_start < start || break_end > end)
+            {
+                diagnostics.push(warning(break_path, format!("'{}' is not inside the term", brk.name)));
+            }
+        }
+        for &(j, other_start, other_end) in &spans {
+            if start <= other_end && other_start <= end {
+                diagnostics.push(warning(path.clone(), format!("overlaps terms[{}]; the earlier term wins", j)));
+            }
+        }
+        spans.push((i, start, end));
+    }
+}
+
+fn check_holiday_calendar(config: &Config, now: DateTime<Local>, diagnostics: &mut Vec<Diagnostic>) {
+    let Some(calendar) = &config.holiday_calendar else {
+        return;
+    };
+    if let Some(bundled) = &calendar.bundled {
+        let year = Zone::of_config(config).wall_time(now).year();
+        if !BUNDLED_CALENDARS.iter().any(|(id, _, _)| id == bundled) {
+            let mut known: Vec<&str> = BUNDLED_CALENDARS.iter().map(|(id, _, _)| *id).collect();
+            known.dedup();
+            diagnostics.push(error(
+                "$.holiday_calendar.bundled".to_string(),
+                format!("unknown calendar '{}' (available: {})", bundled, known.join(", ")),
+            ));
+        } else if !holidays::bundled_covers(bundled, year) {
+            diagnostics.push(warning(
+                "$.holiday_calendar.bundled".to_string(),
+                format!("'{}' has no data for {}, so this year's holidays are regular days", bundled, year),
+            ));
+        }
+    }
+    if let Some(file) = &calendar.file
+        && !(file.to_lowercase().ends_with(".json") || file.to_lowercase().ends_with(".ics"))
+    {
+        diagnostics.push(error(
+            "$.holiday_calendar.file".to_string(),
+            format!("'{}' is neither a .json nor an .ics file", file),
+        ));
+    }
+    for (field, value) in [("workday_as", &calendar.workday_as), ("holiday_as", &calendar.holiday_as)] {
+        if let Some(value) = value
+            && weekday_from_str(value).is_none()
+        {
+            diagnostics.push(error(
+                format!("$.holiday_calendar.{}", field),
+                format!("'{}' is not a weekday name", value),
+            ));
+        }
+    }
+    for (i, entry) in calendar.entries.iter().enumerate() {
+        let path = format!("$.holiday_calendar.entries[{}]", i);
+        if let Err(e) = DateKey::parse(&entry.date) {
+            diagnostics.push(error(format!("{}.date", path), e));
+        }
+        let behaves_as = entry.behaves_as.to_lowercase();
+        if !matches!(behaves_as.as_str(), "holiday" | "workday") && weekday_from_str(&behaves_as).is_none() {
+            diagnostics.push(error(
+                format!("{}.as", path),
+                format!("'{}' is not \"holiday\", \"workday\" or a weekday name", entry.behaves_as),
+            ));
+        }
+    }
+}
+
+fn check_span(path: &str, start: &str, end: &str, diagnostics: &mut Vec<Diagnostic>) -> Option<(NaiveDate, NaiveDate)> {
+    let mut parse = |field: &str, value: &str| {
+        let parsed = parse_day(value);
+        if parsed.is_none() {
+            diagnostics.push(error(
+                format!("{}.{}", path, field),
+                format!("'{}' is not a valid YYYY-MM-DD date", value),
+            ));
+        }
+        parsed
+    };
+    let (start, end) = (parse("start", start)?, parse("end", end)?);
+    if end < start {
+        diagnostics.push(error(path.to_string(), format!("end {} is before start {}", end, start)));
+        return None;
+    }
+    Some((start, end))
+}
+
+fn is_scoped(period: &SpecialPeriod) -> bool {
+    !period.dates.is_empty()
+        || period.from.is_some()
+        || period.until.is_some()
+        || period.every_n_weeks.is_some()
+        || period.week_parity.is_some()
+        || period.weeks.is_some()
+        || period.during.is_some()
+}
+
+fn check_date(path: &str, value: &str, diagnostics: &mut Vec<Diagnostic>) -> Option<NaiveDate> {
+    match DateKey::parse(value) {
+        Ok(DateKey::Fixed { start, end }) if start == end => Some(start),
+        _ => {
+            diagnostics.push(error(path.to_string(), format!("'{}' is not a valid YYYY-MM-DD date", value)));
+            None
+        }
+    }
+}
+
+fn check_timezone(path: &str, timezone: Option<&str>, diagnostics: &mut Vec<Diagnostic>) {
+    if let Err(e) = Zone::parse(timezone) {
+        diagnostics.push(error(path.to_string(), e));
+    }
+}
+
+/// `HH:MM` or a sun time, which needs `$.location`.
+fn check_time_spec(config: &Config, path: &str, value: &str, diagnostics: &mut Vec<Diagnostic>) -> Option<TimeSpec> {
+    match TimeSpec::parse(value) {
+        Ok(spec) => {
+            if spec.is_solar() && config.location.is_none() {
+                diagnostics.push(error(path.to_string(), format!("'{}' needs $.location", value)));
+            }
+            Some(spec)
+        }
+        Err(e) => {
+            diagnostics.push(error(path.to_string(), e));
+            None
+        }
+    }
+}
+
+fn check_time(path: &str, value: &str, diagnostics: &mut Vec<Diagnostic>) -> Option<NaiveTime> {
+    match NaiveTime::parse_from_str(value, "%H:%M") {
+        Ok(time) => Some(time),
+        Err(_) => {
+            diagnostics.push(error(path.to_string(), format!("'{}' is not a valid HH:MM time", value)));
+            None
+        }
+    }
+}
+
+/// A target is either a URL, a generated wallpaper, a key of `special_urls` or
+/// `playlists`, or empty to clear the wallpaper.
+fn check_target(config: &Config, path: &str, target: &str, diagnostics: &mut Vec<Diagnostic>) {
+    if target.is_empty() || is_url(target) {
+        return;
+    }
+    if generate::is_generated(target) {
+        if let Err(e) = Generated::parse(target, NaiveDate::default()) {
+            diagnostics.push(error(path.to_string(), e));
+        }
+        return;
+    }
+    if !config.special_urls.contains_key(target) && !config.playlists.contains_key(target) {
+        diagnostics.push(error(
+            path.to_string(),
+            format!("'{}' is neither a URL nor an id in special_urls or playlists", target),
+        ));
+    }
+}
+
+/// An http(s) or file URL, the kinds of wallpaper target that are fetched or read.
+pub(crate) fn is_url(value: &str) -> bool {
+    url::Url::parse(value)
+        .map(|u| matches!(u.scheme(), "http" | "https" | "file"))
+        .unwrap_or(false)
+}
+
+fn error(path: String, message: String) -> Diagnostic {
+    Diagnostic {
+        severity: Severity::Error,
+        path,
+        message,
+    }
+}
+
+fn warning(path: String, message: String) -> Diagnostic {
+    Diagnostic {
+        severity: Severity::Warning,
+        path,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timezone::Zone;
+    use chrono::NaiveDateTime;
+
+    fn now() -> DateTime<Local> {
+        let naive = NaiveDateTime::parse_from_str("2026-10-18 12:00", "%Y-%m-%d %H:%M").unwrap();
+        Zone::Named(chrono_tz::Asia::Shanghai).instant(naive).unwrap()
+    }
+
+    /// `(severity, path)` of every diagnostic for a config with `extra` fields.
+    fn diagnose(wallpapers: &str, extra: &str) -> Vec<(Severity, String)> {
+        let text = format!(
+            r#"{{"version": 1, "timezone": "Asia/Shanghai", "wallpapers": {{{}}}{}}}"#,
+            wallpapers, extra
+        );
+        let (_, diagnostics) = parse_and_validate(&text, now());
+        diagnostics.into_iter().map(|d| (d.severity, d.path)).collect()
+    }
+
+    fn has(diagnostics: &[(Severity, String)], severity: Severity, path: &str) -> bool {
+        diagnostics.iter().any(|(s, p)| *s == severity && p == path)
+    }
+
+    #[test]
+    fn a_complete_config_is_clean() {
+        let diagnostics = diagnose(
+            r#""days": {"default": "https://example.com/a.jpg"},
+               "specials": [{"day": "friday", "start": "18:00", "end": "20:00", "url": "lobby"}]"#,
+            r#", "special_urls": {"lobby": "https://example.com/lobby.html"}"#,
+        );
+        assert_eq!(diagnostics, []);
+    }
+
+    #[test]
+    fn targets_must_be_urls_or_known_ids() {
+        let diagnostics = diagnose(
+            r#""days": {"default": "lobby", "monday": "special9", "tuesday": "color:#123456", "friday": "color:nope"}"#,
+            r#", "special_urls": {"lobby": "https://example.com/lobby.html", "bad": "lobby.html"}"#,
+        );
+        assert!(!has(&diagnostics, Severity::Error, "$.wallpapers.days.default"));
+        assert!(has(&diagnostics, Severity::Error, "$.wallpapers.days.monday"));
+        assert!(!has(&diagnostics, Severity::Error, "$.wallpapers.days.tuesday"));
+        assert!(has(&diagnostics, Severity::Error, "$.wallpapers.days.friday"));
+        assert!(has(&diagnostics, Severity::Error, "$.special_urls.bad"));
+    }
+
+    #[test]
+    fn uncovered_days_and_bad_dates() {
+        let diagnostics = diagnose(r#""days": {"weekdays": ""}, "dates": {"13-01": "", "10-01": ""}"#, "");
+        assert!(has(&diagnostics, Severity::Warning, "$.wallpapers.days"));
+        assert!(has(&diagnostics, Severity::Error, "$.wallpapers.dates[\"13-01\"]"));
+        assert!(!has(&diagnostics, Severity::Error, "$.wallpapers.dates[\"10-01\"]"));
+    }
+
+    #[test]
+    fn special_periods() {
+        let diagnostics = diagnose(
+            r#""days": {"default": ""}, "specials": [
+                {"day": "funday", "start": "25:00", "end": "20:00", "url": ""},
+                {"day": "friday", "start": "08:00", "end": "08:00", "url": ""},
+                {"day": "monday", "start": "08:00", "end": "12:00", "url": ""},
+                {"day": "monday", "start": "11:00", "end": "13:00", "url": ""},
+                {"day": "monday", "start": "11:00", "end": "13:00", "weeks": "1-4", "url": ""},
+                {"repeat": "* * 8", "start": "08:00", "end": "09:00", "url": ""},
+                {"day": "monday", "start": "08:00", "end": "09:00", "from": "2026-12-01", "until": "2026-11-01", "url": ""}
+            ]"#,
+            "",
+        );
+        assert!(has(&diagnostics, Severity::Error, "$.wallpapers.specials[0].day"));
+        assert!(has(&diagnostics, Severity::Error, "$.wallpapers.specials[0].start"));
+        assert!(has(&diagnostics, Severity::Error, "$.wallpapers.specials[1]"));
+        assert!(has(&diagnostics, Severity::Warning, "$.wallpapers.specials[3]"));
+        // Scoped periods are not checked for overlaps, but weeks need terms.
+        assert!(!has(&diagnostics, Severity::Warning, "$.wallpapers.specials[4]"));
+        assert!(has(&diagnostics, Severity::Error, "$.wallpapers.specials[4]"));
+        assert!(has(&diagnostics, Severity::Error, "$.wallpapers.specials[5].repeat"));
+        assert!(has(&diagnostics, Severity::Error, "$.wallpapers.specials[6]"));
+    }
+
+    #[test]
+    fn rules_playlists_and_holidays() {
+        let diagnostics = diagnose(
+            r#""days": {"default": ""}"#,
+            r#", "rules": [{"name": "x", "priority": 1, "start": "08:00", "weekdays": ["mon"], "url": ""}],
+               "playlists": {"empty": {"items": [], "interval_minutes": 0}},
+               "holiday_calendar": {"bundled": "xx"},
+               "location": {"latitude": 91, "longitude": 0}"#,
+        );
+        assert!(has(&diagnostics, Severity::Error, "$.rules[0].end"));
+        assert!(has(&diagnostics, Severity::Error, "$.playlists.empty.items"));
+        assert!(has(&diagnostics, Severity::Error, "$.playlists.empty.interval_minutes"));
+        assert!(has(&diagnostics, Severity::Error, "$.holiday_calendar.bundled"));
+        assert!(has(&diagnostics, Severity::Error, "$.location.latitude"));
+    }
+
+    #[test]
+    fn migrations_are_warnings_and_parse_errors_point_at_the_root() {
+        let (config, diagnostics) =
+            parse_and_validate(r#"{"wallpapers": {"days": {"default": ""}, "periods": []}}"#, now());
+        assert!(config.is_some());
+        assert!(diagnostics.iter().all(|d| d.severity == Severity::Warning));
+        assert!(diagnostics.iter().any(|d| d.path == "$.wallpapers.periods"));
+        let (config, diagnostics) = parse_and_validate("{", now());
+        assert!(config.is_none());
+        assert_eq!(diagnostics[0].path, "$");
+        assert!(has_errors(&diagnostics));
+    }
+}
@@ -1,7 +1,11 @@
-use crate::autostart::to_wide_string;
-use crate::file_manager::{download_file, wallpaper_url_to_path};
+use crate::backend::{WallpaperBackend, WallpaperSource};
+use crate::file_manager::{download_file, generated_wallpaper_path, wallpaper_url_to_path};
+use crate::generate::{self, Generated, DEFAULT_SIZE};
+use crate::playlist::{PlaylistItem, Step};
+use crate::schedule::{self, MatchedRule};
+use crate::sources::{self, DirectoryWatcher};
 use crate::state::AppState;
-use chrono::{Local, NaiveTime, Datelike, Weekday};
+use crate::timezone::Zone;
 use log::{debug, error, info, warn};
 use reqwest::Client;
 use std::path::{Path, PathBuf};
@@ -11,15 +15,23 @@ use std::time::Duration;
 use sysinfo::{Pid, System};
 use tokio::select;
 use tokio::time::sleep;
-use windows::Win32::System::Com;
-use windows::Win32::UI::WindowsAndMessaging::{
-    SystemParametersInfoW, SPI_SETDESKWALLPAPER, SPIF_SENDCHANGE, SPIF_UPDATEINIFILE,
-};
+
+#[cfg(windows)]
+const WEB_EXE_NAME: &str = "daily_web.exe";
+#[cfg(not(windows))]
+const WEB_EXE_NAME: &str = "daily_web";
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+// The tokio timer does not advance while a laptop is suspended, so never trust a
+// single sleep for longer than this.
+const MAX_SLEEP: Duration = Duration::from_secs(15 * 60);
 
 pub async fn wallpaper_loop(state: Arc<AppState>) {
     let client = Client::new();
     let mut sys = System::new_all();
+    let mut watcher = DirectoryWatcher::new(state.clone());
+    #[cfg(windows)]
     unsafe {
+        use windows::Win32::System::Com;
         if Com::CoInitializeEx(None, Com::COINIT_APARTMENTTHREADED).is_err() {
             error!("Failed to initialize COM");
             return;
@@ -27,35 +39,80 @@ pub async fn wallpaper_loop(state: Arc<AppState>) {
     }
 
 loop {
-        let (target_url_id, is_temp) = match determine_target_url(&state).await {
-            Some(url) => url,
+        let (target, rule) = match determine_target_url(&state).await {
+            Some(target) => target,
             None => {
                 warn!("Could not determine target wallpaper. Config may be missing.");
-                wait_for_next_check(&state, Duration::from_secs(300)).await;
+                // A config arriving wakes the loop; otherwise nothing changes before the
+                // next transition.
+                let wait_duration = time_until_next_transition(&state).await;
+                wait_for_next_check(&state, wait_duration).await;
+                continue;
+            }
+        };
+        let mut watch_dirs = Vec::new();
+        let (item, rotation_due) = match step_playlist(&state, &target, &mut watch_dirs).await {
+            Ok(Some(step)) => {
+                prefetch(&client, &state, &step.next);
+                (step.item, Some(step.due))
+            }
+            Ok(None) => (target, None),
+            Err(e) => {
+                warn!("{}", e);
+                watcher.watch(watch_dirs);
+                wait_for_next_check(&state, RETRY_INTERVAL).await;
                 continue;
             }
         };
-        let is_special = target_url_id.starts_with("special") || target_url_id.ends_with(".html");
+        if let Some(dir) = sources::file_path(&item).filter(|path| path.is_dir()) {
+            watch_dirs.push(dir);
+        }
+        watcher.watch(watch_dirs);
+        let today = schedule_date(&state).await;
+        let target_url_id = generate::pin_date(&sources::resolve_directory(&item), today);
+        let is_special = sources::is_web_page(state.config.lock().await.as_ref(), &target_url_id);
+        let source = WallpaperSource {
+            url: target_url_id.clone(),
+            rule,
+        };
         let mut current_url_lock = state.current_wallpaper_url.lock().await;
         let mut pid_lock = state.web_wallpaper_pid.lock().await;
         if *current_url_lock != target_url_id {
             info!("Wallpaper change requested: {} -> {}", *current_url_lock, target_url_id);
-            if is_special {
-                let html_url = match resolve_special_url(&state, &target_url_id).await {
-                    Some(url) => url,
-                    None => {
-                        error!("Could not resolve special URL for '{}'", target_url_id);
-                        continue;
-                    }
-                };
+            if target_url_id.is_empty() {
                 kill_web_wallpaper(&mut sys, &mut pid_lock);
-                info!("Launching web wallpaper: {}", html_url);
-                if let Err(e) = launch_web_wallpaper(&mut pid_lock, &html_url) {
-                    error!("Failed to launch daily_web.exe: {}", e);
+                info!("Empty wallpaper target, clearing desktop wallpaper.");
+                if let Err(e) = clear_wallpaper(state.backend.as_ref()) {
+                    error!("Failed to clear wallpaper: {}", e);
                 } else {
                     *current_url_lock = target_url_id;
                 }
-
+            } else if is_special {
+                match resolve_special_url(&state, &target_url_id).await {
+                    Some(html_url) => {
+                        kill_web_wallpaper(&mut sys, &mut pid_lock);
+                        match state.backend.set_web_page(&html_url, &source) {
+                            Ok(true) => {
+                                info!("Web wallpaper handled by {} backend: {}", state.backend.name(), html_url);
+                                *current_url_lock = target_url_id;
+                            }
+                            Ok(false) => {
+                                info!("Launching web wallpaper: {}", html_url);
+                                if let Err(e) = launch_web_wallpaper(&mut pid_lock, &html_url) {
+                                    error!("Failed to launch {}: {}", WEB_EXE_NAME, e);
+                                } else {
+                                    *current_url_lock = target_url_id;
+                                }
+                            }
+                            Err(e) => {
+                                error!("Failed to set web wallpaper: {}", e);
+                            }
+                        }
+                    }
+                    None => {
+                        error!("Could not resolve special URL for '{}'", target_url_id);
+                    }
+                }
             } else {
                 if pid_lock.is_some() {
                     info!("Terminating web wallpaper process...");
@@ -63,37 +120,33 @@ loop {
                     *pid_lock = None;
                 }
                 info!("Setting image wallpaper: {}", target_url_id);
-                let image_path = wallpaper_url_to_path(&state.app_data_dir, &target_url_id);
-                match download_file(&client, &target_url_id, &image_path).await {
-                    Ok(_) => {
-                        if let Err(e) = set_wallpaper_image(Some(&image_path)) {
+                match fetch_image(&client, &state, &target_url_id).await {
+                    Ok(image_path) => {
+                        if let Err(e) = set_wallpaper_image(state.backend.as_ref(), &image_path, &source) {
                             error!("Failed to set image wallpaper: {}", e);
                         } else {
-                            set_wallpaper_lock(true).ok();
+                            state.backend.set_lock(true).ok();
                             *current_url_lock = target_url_id;
                             info!("Image wallpaper change successful.");
                         }
                     }
                     Err(e) => {
-                        error!("Failed to download wallpaper {}: {}", target_url_id, e);
+                        error!("Failed to fetch wallpaper {}: {}", target_url_id, e);
                     }
                 }
             }
         }
 
+        let in_sync = *current_url_lock == source.url;
         drop(current_url_lock);
         drop(pid_lock);
-        let wait_duration = if is_temp {
-            if let Some(temp) = state.temp_wallpaper.lock().await.clone() {
-                let duration = temp.expiry - Local::now();
-                Duration::from_secs(duration.num_seconds().max(1) as u64)
-            } else {
-                Duration::from_secs(60)
-            }
-        } else {
-            Duration::from_secs(60)
-        };
-        
+        let mut wait_duration = time_until_next_transition(&state).await;
+        if !in_sync {
+            wait_duration = wait_duration.min(RETRY_INTERVAL);
+        }
+        if let Some(due) = rotation_due {
+            wait_duration = wait_duration.min((due - state.clock.now()).to_std().unwrap_or(Duration::ZERO));
+        }
         wait_for_next_check(&state, wait_duration).await;
     }
 }
@@ -109,18 +162,18 @@ async fn resolve_special_url(state: &Arc<AppState>, url_id: &str) -> Option<Stri
 fn launch_web_wallpaper(pid_lock: &mut Option<u32>, url: &str) -> Result<(), String> {
     let mut exe_path = std::env::current_exe().map_err(|e| e.to_string())?;
     exe_path.pop();
-    exe_path.push("daily_web.exe");
+    exe_path.push(WEB_EXE_NAME);
 
     if !exe_path.exists() {
-        warn!("daily_web.exe not found at {}", exe_path.display());
+        warn!("{} not found at {}", WEB_EXE_NAME, exe_path.display());
         // On Windows prefer APPDATA location for packaged `daily_web.exe`
         let app_data_path = std::env::var("APPDATA")
             .map(PathBuf::from)
-            .map(|p| p.join("DailyWallpaper").join("daily_web.exe"))
+            .map(|p| p.join("DailyWallpaper").join(WEB_EXE_NAME))
             .map_err(|e| e.to_string())?;
         
         if !app_data_path.exists() {
-             return Err(format!("daily_web.exe not found at {} or {}", exe_path.display(), app_data_path.display()));
+             return Err(format!("{} not found at {} or {}", WEB_EXE_NAME, exe_path.display(), app_data_path.display()));
         }
         exe_path = app_data_path;
     }
@@ -144,32 +197,121 @@ fn kill_web_wallpaper(sys: &mut System, pid_lock: &mut Option<u32>) {
     }
     *pid_lock = None;
 }
-fn set_wallpaper_image(path: Option<&Path>) -> Result<(), String> {
-    set_wallpaper_lock(false).map_err(|e| format!("Failed to unlock wallpaper: {}", e))?;
+fn set_wallpaper_image(backend: &dyn WallpaperBackend, path: &Path, source: &WallpaperSource) -> Result<(), String> {
+    backend.set_lock(false).map_err(|e| format!("Failed to unlock wallpaper: {}", e))?;
+    backend.set_image(path, source)
+}
+
+fn clear_wallpaper(backend: &dyn WallpaperBackend) -> Result<(), String> {
+    backend.set_lock(false).map_err(|e| format!("Failed to unlock wallpaper: {}", e))?;
+    backend.clear()
+}
+
+async fn time_until_next_transition(state: &Arc<AppState>) -> Duration {
+    let now = state.clock.now();
+    let temp = state.temp_wallpaper.lock().await.clone();
+    let config_lock = state.config.lock().await;
+    match schedule::next_transition(config_lock.as_ref(), temp.as_ref(), now) {
+        Some(at) => {
+            debug!("Next scheduled transition at {}", at);
+            (at - now).to_std().unwrap_or(Duration::ZERO).min(MAX_SLEEP)
+        }
+        None => MAX_SLEEP,
+    }
+}
 
-    let path_wide = match path {
-        Some(p) => {
-            let path_str = p.to_str().ok_or("Invalid path string")?;
-            to_wide_string(path_str)
-        },
+/// `file://` images are used where they are; everything else goes through the cache.
+async fn fetch_image(client: &Client, state: &Arc<AppState>, url: &str) -> Result<PathBuf, String> {
+    if generate::is_generated(url) {
+        // `url` is pinned already, so the date is not used.
+        let generated = Generated::parse(url, state.clock.now().date_naive())?;
+        let (width, height) = state.backend.screen_size().unwrap_or(DEFAULT_SIZE);
+        let path = generated_wallpaper_path(&state.app_data_dir, url, width, height);
+        if !path.exists() {
+            info!("Rendering {} at {}x{}", url, width, height);
+            let bytes = tokio::task::spawn_blocking(move || generated.render_bmp(width, height))
+                .await
+                .map_err(|e| e.to_string())?;
+            tokio::fs::write(&path, bytes)
+                .await
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        }
+        return Ok(path);
+    }
+    match sources::file_path(url) {
+        Some(path) if path.is_dir() => Err(format!("no images in {}", path.display())),
+        Some(path) if !path.is_file() => Err(format!("{} does not exist", path.display())),
+        Some(path) => Ok(path),
         None => {
-            to_wide_string("")
+            let image_path = wallpaper_url_to_path(&state.app_data_dir, url);
+            download_file(client, url, &image_path).await?;
+            Ok(image_path)
         }
+    }
+}
+
+/// Today in the schedule's timezone, which `seed=date` follows.
+async fn schedule_date(state: &Arc<AppState>) -> chrono::NaiveDate {
+    let now = state.clock.now();
+    let config_lock = state.config.lock().await;
+    match config_lock.as_ref() {
+        Some(config) => Zone::of_config(config).wall_time(now).date(),
+        None => now.date_naive(),
+    }
+}
+
+/// Resolves a playlist name to the item it is on, moving the rotation along.
+/// `Ok(None)` means `target` is not a playlist; a playlist's directory is added to
+/// `watch_dirs`.
+async fn step_playlist(state: &Arc<AppState>, target: &str, watch_dirs: &mut Vec<PathBuf>) -> Result<Option<Step>, String> {
+    let playlist = state.config.lock().await.as_ref().and_then(|c| c.playlists.get(target).cloned());
+    let Some(mut playlist) = playlist else {
+        return Ok(None);
     };
-    let result = unsafe {
-        SystemParametersInfoW(
-            SPI_SETDESKWALLPAPER,
-            0,
-            Some(path_wide.as_ptr() as *mut _),
-            SPIF_UPDATEINIFILE | SPIF_SENDCHANGE,
-        )
+    // Listing a directory on a slow disk must not hold up the config.
+    if let Some(dir) = playlist.directory.as_deref().and_then(sources::file_path) {
+        let listed = dir.clone();
+        let images = tokio::task::spawn_blocking(move || sources::images(&listed))
+            .await
+            .map_err(|e| e.to_string())?;
+        playlist.items.extend(images.into_iter().map(PlaylistItem::Url));
+        watch_dirs.push(dir);
+    }
+    let mut rotations = state.rotations.lock().await;
+    let Some((step, changed)) = rotations.step(target, &playlist, state.clock.now()) else {
+        return Err(format!("Playlist {} has nothing to show", target));
     };
+    if changed {
+        debug!("Playlist {} is on {}, next {} at {}", target, step.item, step.next, step.due);
+        if let Err(e) = rotations.save(&state.app_data_dir) {
+            error!("Failed to save playlist positions: {}", e);
+        }
+    }
+    Ok(Some(step))
+}
 
-    if result.is_ok() {
-        Ok(())
-    } else {
-        Err("SystemParametersInfoW call failed".to_string())
+/// Downloads a playlist's next image in the background so the switch is instant.
+fn prefetch(client: &Client, state: &Arc<AppState>, url: &str) {
+    if url.is_empty()
+        || sources::file_path(url).is_some()
+        || generate::is_generated(url)
+    {
+        return;
+    }
+    let path = wallpaper_url_to_path(&state.app_data_dir, url);
+    if path.exists() {
+        return;
     }
+    let (client, state, url) = (client.clone(), state.clone(), url.to_string());
+    tokio::spawn(async move {
+        if sources::is_web_page(state.config.lock().await.as_ref(), &url) {
+            return;
+        }
+        match download_file(&client, &url, &path).await {
+            Ok(()) => debug!("Prefetched {}", url),
+            Err(e) => warn!("Failed to prefetch {}: {}", url, e),
+        }
+    });
 }
 
 async fn wait_for_next_check(state: &Arc<AppState>, duration: Duration) {
@@ -184,89 +326,24 @@ async fn wait_for_next_check(state: &Arc<AppState>, duration: Duration) {
     }
 }
 
-async fn determine_target_url(state: &Arc<AppState>) -> Option<(String, bool)> {
-    let now = Local::now();
-    
-    {
-        let mut temp_lock = state.temp_wallpaper.lock().await;
-        if let Some(temp) = temp_lock.as_ref() {
-            if now < temp.expiry {
-                debug!("Using Temp Wallpaper: {}", temp.url);
-                return Some((temp.url.clone(), true));
-            } else {
-                info!("Temp wallpaper expired.");
-                *temp_lock = None;
-            }
-        }
-    }
-
-    let config_lock = state.config.lock().await;
-    let config = match config_lock.as_ref() {
-        Some(c) => c,
-        None => return None,
-    };
+async fn determine_target_url(state: &Arc<AppState>) -> Option<(String, MatchedRule)> {
+    let now = state.clock.now();
 
-    let current_weekday = now.weekday();
-    let current_time = now.time();
-    for period in &config.wallpapers.specials { //
-        if weekday_from_str(&period.day) == Some(current_weekday) { //
-            if let (Ok(start), Ok(end)) = (
-                NaiveTime::parse_from_str(&period.start, "%H:%M"), //
-                NaiveTime::parse_from_str(&period.end, "%H:%M"), //
-            ) {
-                if current_time >= start && current_time < end {
-                    debug!("Using Period Wallpaper: {}", period.url);
-                    return Some((period.url.clone(), false)); //
-                }
-            }
+    let temp = {
+        let mut temp_lock = state.temp_wallpaper.lock().await;
+        if let Some(temp) = temp_lock.as_ref()
+            && now >= temp.expiry
+        {
+            info!("Temp wallpaper expired.");
+            *temp_lock = None;
         }
-    }
-    let date_key = now.format("%m-%d").to_string();
-    if let Some(url) = config.wallpapers.dates.get(&date_key) { 
-        debug!("Using Date Wallpaper: {}", url);
-        return Some((url.clone(), false));
-    }
-    let day_url = match current_weekday {
-        Weekday::Mon => &config.wallpapers.days.monday, 
-        Weekday::Tue => &config.wallpapers.days.tuesday, 
-        Weekday::Wed => &config.wallpapers.days.wednesday, 
-        Weekday::Thu => &config.wallpapers.days.thursday, 
-        Weekday::Fri => &config.wallpapers.days.friday, 
-        Weekday::Sat => &config.wallpapers.days.saturday, 
-        Weekday::Sun => &config.wallpapers.days.sunday, 
+        temp_lock.clone()
     };
-    debug!("Using Day Wallpaper: {}", day_url);
-    Some((day_url.clone(), false))
-}
 
-fn weekday_from_str(s: &str) -> Option<Weekday> {
-    match s.to_lowercase().as_str() {
-        "monday" => Some(Weekday::Mon),
-        "tuesday" => Some(Weekday::Tue),
-        "wednesday" => Some(Weekday::Wed),
-        "thursday" => Some(Weekday::Thu),
-        "friday" => Some(Weekday::Fri),
-        "saturday" => Some(Weekday::Sat),
-        "sunday" => Some(Weekday::Sun),
-        _ => None,
+    let config_lock = state.config.lock().await;
+    let target = schedule::resolve(config_lock.as_ref(), temp.as_ref(), now);
+    if let Some((url, rule)) = &target {
+        debug!("Using {} wallpaper: {}", rule, url);
     }
+    target
 }
-
-
-
-fn set_wallpaper_lock(lock: bool) -> Result<(), String> {
-    let key = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Policies\ActiveDesktop";
-    let value = "NoChangingWallPaper";
-    let data = if lock { "1" } else { "0" };
-    let output = std::process::Command::new("reg")
-        .args(["add", key, "/v", value, "/t", "REG_DWORD", "/d", data, "/f"]) 
-        .output()
-        .map_err(|e| format!("Failed to run reg add for wallpaper lock: {}", e))?;
-
-    if output.status.success() {
-        debug!("Wallpaper lock set to: {}", lock);
-        Ok(())
-    } else {
-        Err(format!("Failed to set NoChangingWallPaper: {}", String::from_utf8_lossy(&output.stderr)))
-    }
-}
\ No newline at end of file
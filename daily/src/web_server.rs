@@ -1,13 +1,23 @@
+use crate::backend::AppliedWallpaper;
+use crate::config::Config;
 use crate::logger::read_logs;
+use crate::overrides::{LayerEntry, Origin, Provenance};
+use crate::schedule::{self, parse_datetime, start_of_day, Decision, MatchedRule, RuleCheck, Segment};
 use crate::state::{AppState, TempWallpaper};
+use crate::holidays;
+use crate::ics;
+use crate::rules::TEMP_PRIORITY;
+use crate::terms;
+use crate::timezone::Zone;
+use crate::validate::{has_errors, parse_and_validate, Diagnostic};
 use axum::{
-    extract::State,
-    http::StatusCode,
-    response::Html,
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse},
     routing::{get, post},
     Json, Router,
 };
-use chrono::{Duration, Local};
+use chrono::{DateTime, Duration, Local};
 use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
 use std::sync::Arc;
@@ -16,6 +26,12 @@ pub async fn start_server(state: Arc<AppState>) -> Result<(), String> {
     let app = Router::new()
         .route("/", get(handle_root))
         .route("/api/temp_wallpaper", post(handle_set_temp_wallpaper))
+        .route("/api/schedule", get(handle_schedule))
+        .route("/api/schedule.ics", get(handle_schedule_ics))
+        .route("/api/explain", get(handle_explain))
+        .route("/api/config/validate", post(handle_validate_config))
+        .route("/api/config/layers", get(handle_config_layers))
+        .route("/api/history", get(handle_history))
         .with_state(state);
 
     let addr = SocketAddr::from(([0, 0, 0, 0], 11452));
@@ -35,7 +51,7 @@ pub struct TempWallpaperPayload {
     hours: Option<u32>, //
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Serialize)]
 pub struct ApiResponse {
     success: bool,
     message: String,
@@ -46,7 +62,7 @@ async fn handle_set_temp_wallpaper(
     Json(payload): Json<TempWallpaperPayload>,
 ) -> (StatusCode, Json<ApiResponse>) {
     let hours = payload.hours.unwrap_or(1); // 默认为1小时
-    let expiry = Local::now() + Duration::hours(hours as i64);
+    let expiry = state.clock.now() + Duration::hours(hours as i64);
 
     let mut temp_lock = state.temp_wallpaper.lock().await;
     *temp_lock = Some(TempWallpaper {
@@ -71,8 +87,218 @@ async fn handle_set_temp_wallpaper(
     )
 }
 
+const MAX_SCHEDULE_DAYS: u32 = 62;
+
+type ApiError = (StatusCode, Json<ApiResponse>);
+
+#[derive(Deserialize)]
+pub struct ScheduleQuery {
+    from: Option<String>,
+    days: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct ScheduleResponse {
+    from: DateTime<Local>,
+    until: DateTime<Local>,
+    segments: Vec<Segment>,
+}
+
+async fn handle_schedule(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ScheduleQuery>,
+) -> Result<Json<ScheduleResponse>, (StatusCode, Json<ApiResponse>)> {
+    let temp = state.temp_wallpaper.lock().await.clone();
+    let config_lock = state.config.lock().await;
+    let (from, until) = schedule_window(&state, config_lock.as_ref(), query, 7)?;
+    let segments = schedule::timeline(config_lock.as_ref(), temp.as_ref(), from, until);
+    Ok(Json(ScheduleResponse { from, until, segments }))
+}
+
+/// `from` and today are read in the config's timezone, like the schedule itself.
+fn schedule_window(
+    state: &AppState,
+    config: Option<&Config>,
+    query: ScheduleQuery,
+    default_days: u32,
+) -> Result<(DateTime<Local>, DateTime<Local>), ApiError> {
+    let zone = config.map_or(Zone::Local, Zone::of_config);
+    let from = match query.from {
+        Some(from) => parse_datetime(&from, zone).map_err(bad_request)?,
+        None => start_of_day(zone.wall_time(state.clock.now()).date(), zone)
+            .ok_or_else(|| bad_request("Cannot determine the start of today".to_string()))?,
+    };
+    let days = query.days.unwrap_or(default_days).clamp(1, MAX_SCHEDULE_DAYS);
+    Ok((from, from + Duration::days(days as i64)))
+}
+
+/// Specials, dates and temp wallpapers as an iCalendar feed. Weekday defaults are left
+/// out, and output only depends on the query and config so feeds can be diffed.
+async fn handle_schedule_ics(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ScheduleQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse>)> {
+    let temp = state.temp_wallpaper.lock().await.clone();
+    let config_lock = state.config.lock().await;
+    let (from, until) = schedule_window(&state, config_lock.as_ref(), query, 28)?;
+    let segments = schedule::timeline(config_lock.as_ref(), temp.as_ref(), from, until);
+
+    let mut feed = String::new();
+    for line in [
+        "BEGIN:VCALENDAR",
+        "VERSION:2.0",
+        "PRODID:-//Daily Wallpaper//Schedule//EN",
+        "CALSCALE:GREGORIAN",
+        "X-WR-CALNAME:Daily Wallpaper",
+    ] {
+        feed.push_str(&ics::fold(line));
+    }
+    for segment in segments.iter().filter(|s| !matches!(s.rule, MatchedRule::Day { .. })) {
+        let (category, name) = match &segment.rule {
+            MatchedRule::Temp { .. } => ("TEMP", "Temporary wallpaper"),
+            MatchedRule::Special { .. } => ("SPECIAL", "Special"),
+            MatchedRule::Date { .. } => ("DATE", "Date"),
+            MatchedRule::Day { .. } => ("DAY", "Day"),
+            MatchedRule::Rule { .. } => ("RULE", "Rule"),
+        };
+        let target = segment.special_id.as_deref().unwrap_or(&segment.url);
+        let uid = sha256::digest(format!("{}|{}|{}", segment.start.to_rfc3339(), segment.rule, segment.url));
+        let mut lines = vec![
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}@daily-wallpaper", uid),
+            format!("DTSTAMP:{}", ics::format_utc(from)),
+            format!("DTSTART:{}", ics::format_utc(segment.start)),
+            format!("DTEND:{}", ics::format_utc(segment.end)),
+            format!("SUMMARY:{}", ics::escape(&format!("{}: {}", name, target))),
+            format!("DESCRIPTION:{}", ics::escape(&format!("{}\n{}", segment.rule, segment.url))),
+            format!("CATEGORIES:{}", category),
+        ];
+        if segment.url.starts_with("http://") || segment.url.starts_with("https://") {
+            lines.push(format!("URL:{}", segment.url));
+        }
+        lines.push("END:VEVENT".to_string());
+        for line in lines {
+            feed.push_str(&ics::fold(&line));
+        }
+    }
+    feed.push_str(&ics::fold("END:VCALENDAR"));
+    Ok(([(header::CONTENT_TYPE, "text/calendar; charset=utf-8")], feed))
+}
+
+#[derive(Deserialize)]
+pub struct ExplainQuery {
+    at: Option<String>,
+}
+
+async fn handle_explain(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ExplainQuery>,
+) -> Result<Json<Decision>, (StatusCode, Json<ApiResponse>)> {
+    let at = match query.at {
+        Some(at) => {
+            let zone = state.config.lock().await.as_ref().map_or(Zone::Local, Zone::of_config);
+            parse_datetime(&at, zone).map_err(bad_request)?
+        }
+        None => state.clock.now(),
+    };
+    Ok(Json(explain_at(&state, at).await))
+}
+
+async fn explain_at(state: &AppState, at: DateTime<Local>) -> Decision {
+    let temp = state.temp_wallpaper.lock().await.clone();
+    let config_lock = state.config.lock().await;
+    schedule::explain(config_lock.as_ref(), temp.as_ref(), at)
+}
+
+#[derive(Serialize)]
+pub struct ValidationResponse {
+    valid: bool,
+    diagnostics: Vec<Diagnostic>,
+}
+
+async fn handle_validate_config(State(state): State<Arc<AppState>>, body: String) -> Json<ValidationResponse> {
+    let (config, diagnostics) = parse_and_validate(&body, state.clock.now());
+    Json(ValidationResponse {
+        valid: config.is_some() && !has_errors(&diagnostics),
+        diagnostics,
+    })
+}
+
+async fn handle_config_layers(State(state): State<Arc<AppState>>) -> Json<Provenance> {
+    Json(state.provenance.lock().await.clone())
+}
+
+async fn handle_history(State(state): State<Arc<AppState>>) -> Json<Vec<AppliedWallpaper>> {
+    Json(state.backend.history())
+}
+
+fn bad_request(message: String) -> (StatusCode, Json<ApiResponse>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ApiResponse {
+            success: false,
+            message,
+        }),
+    )
+}
+
 async fn handle_root(State(state): State<Arc<AppState>>) -> Html<String> {
     let current_url = state.current_wallpaper_url.lock().await.clone();
+    let desktop_wallpaper = match state.backend.current() {
+        Ok(Some(path)) => path.display().to_string(),
+        Ok(None) => "No wallpaper set.".to_string(),
+        Err(e) => format!("Failed to query wallpaper: {}", e),
+    };
+    let decision = explain_at(&state, state.clock.now()).await;
+    let trace_rows: String = decision
+        .trace
+        .iter()
+        .map(|entry| {
+            let class = match entry.check {
+                RuleCheck::Selected => "selected",
+                RuleCheck::Shadowed => "shadowed",
+                RuleCheck::Skipped(_) => "skipped",
+            };
+            let priority = match entry.priority {
+                TEMP_PRIORITY => "always".to_string(),
+                priority => priority.to_string(),
+            };
+            format!(
+                "<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                class,
+                priority,
+                html_escape(&entry.rule),
+                html_escape(entry.url.as_deref().unwrap_or("")),
+                html_escape(&entry.check.to_string())
+            )
+        })
+        .collect();
+    let provenance = state.provenance.lock().await.clone();
+    let layer_rows: String = provenance
+        .entries
+        .iter()
+        .map(|entry| layer_row(entry, ""))
+        .chain(provenance.blocked.iter().map(|entry| layer_row(entry, "blocked")))
+        .collect();
+    let now = state.clock.now();
+    let zone = state.config.lock().await.as_ref().map(Zone::of_config).unwrap_or(Zone::Local);
+    let clock_status = format!(
+        "Machine time:  {}\nSchedule time: {} ({})",
+        Zone::Local.describe(now),
+        zone.describe(now),
+        zone
+    );
+    let today = zone.wall_time(now).date();
+    let term_status = match state.config.lock().await.as_ref() {
+        Some(config) => {
+            let term = match terms::locate(&config.terms, today) {
+                Some(position) => position.to_string(),
+                None => "No terms configured.".to_string(),
+            };
+            format!("{}\n{}: {}", term, today.format("%Y-%m-%d %A"), holidays::day_kind(config, today))
+        }
+        None => "Config not loaded.".to_string(),
+    };
     let config_json = state.config.lock().await
         .as_ref()
         .and_then(|c| serde_json::to_string_pretty(c).ok())
@@ -96,6 +322,23 @@ async fn handle_root(State(state): State<Arc<AppState>>) -> Html<String> {
                 .section {{ margin-bottom: 20px; }}
                 form input[type="text"], form input[type="number"] {{ width: 300px; padding: 8px; margin-right: 10px; }}
                 form button {{ padding: 8px 12px; }}
+                .calendar {{ display: grid; grid-template-columns: 40px repeat(7, 1fr); gap: 2px; font-size: 12px; }}
+                .calendar .day-name {{ text-align: center; font-weight: bold; }}
+                .calendar .hours, .calendar .day {{ position: relative; height: 720px; }}
+                .calendar .day {{ background: #fafafa; border: 1px solid #ddd; }}
+                .calendar .hour {{ position: absolute; right: 4px; color: #888; }}
+                .calendar .segment {{ position: absolute; left: 2px; right: 2px; overflow: hidden; border-radius: 3px; padding: 0 3px; box-sizing: border-box; color: #fff; }}
+                .segment.temp {{ background: #e67e22; }}
+                .segment.special {{ background: #8e44ad; }}
+                .segment.date {{ background: #27ae60; }}
+                .segment.day {{ background: #2980b9; }}
+                .segment.rule {{ background: #c0392b; }}
+                .trace {{ width: 100%; border-collapse: collapse; font-size: 14px; }}
+                .trace td, .trace th {{ border-bottom: 1px solid #ddd; padding: 4px 8px; text-align: left; word-break: break-all; }}
+                .trace .selected {{ background: #e8f5e9; font-weight: bold; }}
+                .trace .skipped {{ color: #888; }}
+                .trace .override {{ background: #fff8e1; }}
+                .trace .blocked {{ color: #888; text-decoration: line-through; }}
             </style>
         </head>
         <body>
@@ -107,6 +350,35 @@ async fn handle_root(State(state): State<Arc<AppState>>) -> Html<String> {
                     <pre><code>{current_url}</code></pre>
                 </div>
 
+                <div class="section">
+                    <h2>Desktop Wallpaper ({backend_name} backend)</h2>
+                    <pre><code>{desktop_wallpaper}</code></pre>
+                </div>
+
+                <div class="section">
+                    <h2>Clock</h2>
+                    <pre><code>{clock_status}</code></pre>
+                </div>
+
+                <div class="section">
+                    <h2>Term Week and Calendar</h2>
+                    <pre><code>{term_status}</code></pre>
+                </div>
+
+                <div class="section">
+                    <h2>Why This Wallpaper?</h2>
+                    <table class="trace">
+                        <tr><th>Priority</th><th>Rule</th><th>URL</th><th>Result</th></tr>
+                        {trace_rows}
+                    </table>
+                </div>
+
+                <div class="section">
+                    <h2>This Week</h2>
+                    <div id="calendar" class="calendar"></div>
+                    <p><a href="/api/schedule.ics">Subscribe to the schedule (iCalendar, next 4 weeks)</a></p>
+                </div>
+
                 <div class="section">
                     <h2>Set Temporary Wallpaper (API)</h2>
                     <form id="tempForm">
@@ -120,7 +392,15 @@ async fn handle_root(State(state): State<Arc<AppState>>) -> Html<String> {
                 </div>
 
                 <div class="section">
-                    <h2>Current Config (config.json)</h2>
+                    <h2>Config Layers</h2>
+                    <table class="trace">
+                        <tr><th>Path</th><th>Value</th><th>Origin</th></tr>
+                        {layer_rows}
+                    </table>
+                </div>
+
+                <div class="section">
+                    <h2>Effective Config (config.json + override.json)</h2>
                     <pre><code>{config_json}</code></pre>
                 </div>
 
@@ -131,6 +411,77 @@ async fn handle_root(State(state): State<Arc<AppState>>) -> Html<String> {
             </div>
 
             <script>
+                async function loadCalendar() {{
+                    const calendar = document.getElementById('calendar');
+                    const pad = n => String(n).padStart(2, '0');
+                    const monday = new Date();
+                    monday.setHours(0, 0, 0, 0);
+                    monday.setDate(monday.getDate() - ((monday.getDay() + 6) % 7));
+                    const from = monday.getFullYear() + '-' + pad(monday.getMonth() + 1) + '-' + pad(monday.getDate());
+
+                    try {{
+                        const response = await fetch('/api/schedule?from=' + from + '&days=7');
+                        const data = await response.json();
+                        if (!response.ok) {{
+                            calendar.textContent = 'Error: ' + data.message;
+                            return;
+                        }}
+
+                        calendar.appendChild(document.createElement('div'));
+                        const columns = [];
+                        for (let i = 0; i < 7; i++) {{
+                            const date = new Date(monday);
+                            date.setDate(monday.getDate() + i);
+                            const name = document.createElement('div');
+                            name.className = 'day-name';
+                            name.textContent = date.toLocaleDateString(undefined, {{ weekday: 'short', month: 'numeric', day: 'numeric' }});
+                            calendar.appendChild(name);
+                        }}
+                        const hours = document.createElement('div');
+                        hours.className = 'hours';
+                        for (let h = 0; h < 24; h += 2) {{
+                            const label = document.createElement('div');
+                            label.className = 'hour';
+                            label.style.top = (h / 24 * 100) + '%';
+                            label.textContent = pad(h) + ':00';
+                            hours.appendChild(label);
+                        }}
+                        calendar.appendChild(hours);
+                        for (let i = 0; i < 7; i++) {{
+                            const column = document.createElement('div');
+                            column.className = 'day';
+                            calendar.appendChild(column);
+                            columns.push(column);
+                        }}
+
+                        for (const segment of data.segments) {{
+                            let start = new Date(segment.start);
+                            const end = new Date(segment.end);
+                            while (start < end) {{
+                                const midnight = new Date(start);
+                                midnight.setHours(0, 0, 0, 0);
+                                const nextMidnight = new Date(midnight);
+                                nextMidnight.setDate(midnight.getDate() + 1);
+                                const pieceEnd = end < nextMidnight ? end : nextMidnight;
+                                const dayIndex = Math.round((midnight - monday) / 86400000);
+                                if (dayIndex >= 0 && dayIndex < 7) {{
+                                    const block = document.createElement('div');
+                                    block.className = 'segment ' + segment.rule.kind;
+                                    block.style.top = ((start - midnight) / 864000) + '%';
+                                    block.style.height = ((pieceEnd - start) / 864000) + '%';
+                                    block.textContent = pad(start.getHours()) + ':' + pad(start.getMinutes()) + ' ' + (segment.special_id || segment.rule.kind);
+                                    block.title = segment.rule.kind + ': ' + segment.url;
+                                    columns[dayIndex].appendChild(block);
+                                }}
+                                start = pieceEnd;
+                            }}
+                        }}
+                    }} catch (err) {{
+                        calendar.textContent = 'Network error: ' + err;
+                    }}
+                }}
+                loadCalendar();
+
                 document.getElementById('tempForm').addEventListener('submit', async function(e) {{
                     e.preventDefault();
                     const url = document.getElementById('url').value;
@@ -163,6 +514,12 @@ async fn handle_root(State(state): State<Arc<AppState>>) -> Html<String> {
         </html>
         "#,
         current_url = html_escape(&current_url),
+        backend_name = state.backend.name(),
+        trace_rows = trace_rows,
+        clock_status = html_escape(&clock_status),
+        term_status = html_escape(&term_status),
+        layer_rows = layer_rows,
+        desktop_wallpaper = html_escape(&desktop_wallpaper),
         config_json = html_escape(&config_json),
         logs = html_escape(&logs)
     );
@@ -170,10 +527,103 @@ async fn handle_root(State(state): State<Arc<AppState>>) -> Html<String> {
     Html(html)
 }
 
+fn layer_row(entry: &LayerEntry, status: &str) -> String {
+    let origin = match entry.origin {
+        Origin::Remote => "config.json",
+        Origin::Override => "override.json",
+    };
+    let class = if status.is_empty() && entry.origin == Origin::Override { "override" } else { status };
+    let origin = if status.is_empty() { origin.to_string() } else { format!("{} ({})", origin, status) };
+    format!(
+        "<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}</td></tr>",
+        class,
+        html_escape(&entry.path),
+        html_escape(&entry.value),
+        origin
+    )
+}
+
 fn html_escape(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
         .replace('>', "&gt;")
         .replace('"', "&quot;")
         .replace('\'', "&#39;")
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{HeadlessBackend, WallpaperBackend};
+    use crate::clock::ManualClock;
+    use crate::playlist::Rotations;
+    use chrono::TimeZone;
+    use tokio::sync::{Mutex, Notify};
+
+    fn state(backend: HeadlessBackend, app_data_dir: std::path::PathBuf, clock: Arc<ManualClock>) -> Arc<AppState> {
+        Arc::new(AppState {
+            config: Mutex::new(None),
+            remote_config: Mutex::new(None),
+            provenance: Mutex::new(Default::default()),
+            temp_wallpaper: Mutex::new(None),
+            rotations: Mutex::new(Rotations::default()),
+            current_wallpaper_url: Mutex::new(String::new()),
+            written_config: Mutex::new(None),
+            app_data_dir,
+            wallpaper_notify: Notify::new(),
+            web_wallpaper_pid: Mutex::new(None),
+            backend: Box::new(backend),
+            clock,
+        })
+    }
+
+    #[test]
+    fn schedule_window_reads_dates_in_the_config_timezone() {
+        let dir = std::env::temp_dir().join(format!("daily-test-{}-window", std::process::id()));
+        // 20:00 UTC is already 04:00 the next day in Shanghai.
+        let now = chrono::Utc.with_ymd_and_hms(2026, 10, 18, 20, 0, 0).unwrap().with_timezone(&Local);
+        let clock = Arc::new(ManualClock::new(now));
+        let state = state(HeadlessBackend::new(dir.clone(), clock.clone()).unwrap(), dir.clone(), clock);
+        let config = crate::config::parse_config(r#"{"version": 1, "timezone": "Asia/Shanghai", "wallpapers": {}}"#)
+            .unwrap()
+            .0;
+        let utc = |day, hour| chrono::Utc.with_ymd_and_hms(2026, 10, day, hour, 0, 0).unwrap().with_timezone(&Local);
+
+        let query = |from: Option<&str>| ScheduleQuery {
+            from: from.map(str::to_string),
+            days: Some(2),
+        };
+        let (from, until) = schedule_window(&state, Some(&config), query(None), 7).unwrap();
+        assert_eq!((from, until), (utc(18, 16), utc(20, 16)));
+        let (from, _) = schedule_window(&state, Some(&config), query(Some("2026-10-20 08:00")), 7).unwrap();
+        assert_eq!(from, utc(20, 0));
+        assert!(schedule_window(&state, Some(&config), query(Some("tomorrow")), 7).is_err());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn history_lists_applied_wallpapers_oldest_first() {
+        let dir = std::env::temp_dir().join(format!("daily-test-{}-api-history", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        let clock = Arc::new(ManualClock::new(Local::now()));
+        let backend = HeadlessBackend::new(dir.clone(), clock.clone()).unwrap();
+        let source = crate::backend::WallpaperSource {
+            url: "https://example.com/page.html".to_string(),
+            rule: MatchedRule::Date {
+                key: "10-18".to_string(),
+            },
+        };
+        backend.set_web_page(&source.url, &source).unwrap();
+        clock.advance(Duration::minutes(5));
+        backend.clear().unwrap();
+
+        let Json(history) = handle_history(State(state(backend, dir.clone(), clock))).await;
+        let json = serde_json::to_value(&history).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 2);
+        assert_eq!(json[0]["action"], "web_page");
+        assert_eq!(json[0]["page_url"], "https://example.com/page.html");
+        assert_eq!(json[0]["source"]["rule"]["kind"], "date");
+        assert_eq!(json[1]["action"], "clear");
+        assert!(json[1]["source"].is_null());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -14,12 +14,13 @@ fn main() {
         return;
     }
 
-    // On Windows, use `start` to open the default browser. Keep this minimal so the binary compiles
-    // without pulling in GUI dependencies. This is a fallback for the web wallpaper helper.
-    if let Err(e) = std::process::Command::new("cmd")
-        .args(["/C", "start", &url])
-        .spawn()
-    {
+    // Open the default browser: `start` on Windows, `xdg-open` on Linux. Keep this minimal so the binary
+    // compiles without pulling in GUI dependencies. This is a fallback for the web wallpaper helper.
+    #[cfg(windows)]
+    let opened = std::process::Command::new("cmd").args(["/C", "start", &url]).spawn();
+    #[cfg(not(windows))]
+    let opened = std::process::Command::new("xdg-open").arg(&url).spawn();
+    if let Err(e) = opened {
         eprintln!("Failed to open URL {}: {}", url, e);
     }
-}
\ No newline at end of file
+}